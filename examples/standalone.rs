@@ -116,14 +116,12 @@ impl ICourses {
         let html = resp.text().await?;
 
         // Extract AES key from HTML
-        if let Some(start) = html.find("loginVue.loginForm.aesKey") {
-            if let Some(key_start) = html[start..].find('"') {
-                if let Some(key_end) = html[start + key_start + 1..].find('"') {
-                    self.aes_key = html[start + key_start + 1..start + key_start + 1 + key_end]
-                        .as_bytes()
-                        .to_vec();
-                }
-            }
+        if let Some(start) = html.find("loginVue.loginForm.aesKey")
+            && let Some(key_start) = html[start..].find('"')
+            && let Some(key_end) = html[start + key_start + 1..].find('"')
+        {
+            self.aes_key =
+                html.as_bytes()[start + key_start + 1..start + key_start + 1 + key_end].to_vec();
         }
 
         // Get captcha
@@ -153,8 +151,8 @@ impl ICourses {
         let key = GenericArray::from_slice(&self.aes_key);
         let mut buf = [0u8; 128];
         let pt_len = srcs.len();
-        buf[..pt_len].copy_from_slice(&srcs);
-        let ct = Aes128EcbEnc::new(key.into())
+        buf[..pt_len].copy_from_slice(srcs);
+        let ct = Aes128EcbEnc::new(key)
             .encrypt_padded_mut::<Pkcs7>(&mut buf, pt_len)
             .unwrap();
 
@@ -181,27 +179,28 @@ impl ICourses {
 
         let login_resp: LoginResponse = resp.json().await?;
 
-        if login_resp.code == 200 && login_resp.msg == "登录成功" {
-            if let Some(data) = login_resp.data {
-                self.token = data.token;
-                self.batch_list = data.student.elective_batch_list;
-
-                println!("Login success!");
-                println!("=====================================");
-                println!("XH: {}", data.student.XH);
-                println!("XM: {}", data.student.XM);
-                println!("ZYMC: {}", data.student.ZYMC);
+        if login_resp.code == 200
+            && login_resp.msg == "登录成功"
+            && let Some(data) = login_resp.data
+        {
+            self.token = data.token;
+            self.batch_list = data.student.elective_batch_list;
+
+            println!("Login success!");
+            println!("=====================================");
+            println!("XH: {}", data.student.XH);
+            println!("XM: {}", data.student.XM);
+            println!("ZYMC: {}", data.student.ZYMC);
+            println!("=====================================");
+
+            for batch in &self.batch_list {
+                println!("name: {}", batch.name);
+                println!("BeginTime: {}", batch.begin_time);
+                println!("EndTime: {}", batch.end_time);
                 println!("=====================================");
-
-                for batch in &self.batch_list {
-                    println!("name: {}", batch.name);
-                    println!("BeginTime: {}", batch.begin_time);
-                    println!("EndTime: {}", batch.end_time);
-                    println!("=====================================");
-                }
-
-                return Ok(true);
             }
+
+            return Ok(true);
         }
 
         println!("Login failed: {}", login_resp.msg);
@@ -379,6 +378,7 @@ impl ICourses {
     }
 
     // 抢课工作线程
+    #[allow(clippy::too_many_arguments)]
     async fn work_thread(
         client: Client,
         token: String,
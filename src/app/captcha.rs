@@ -0,0 +1,235 @@
+//! Captcha solvers - how a login obtains the captcha answer
+//!
+//! [`login`](super::login) hands the decoded captcha image to a
+//! [`CaptchaSolver`]. Built-in solvers cover the terminal ([`StdinSolver`]),
+//! external tools watching a directory ([`FileDropSolver`]) and GUIs that
+//! answer through a channel ([`ChannelSolver`]).
+
+use crate::error::{ErrorKind, Result};
+use futures::future::BoxFuture;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, mpsc, oneshot};
+
+/// Answers a login captcha
+pub trait CaptchaSolver: Send + Sync {
+    /// Return the answer for the PNG `image` issued with `uuid`
+    fn solve(&self, image: Vec<u8>, uuid: String) -> BoxFuture<'_, Result<String>>;
+}
+
+impl<F> CaptchaSolver for F
+where
+    F: Fn(Vec<u8>, String) -> BoxFuture<'static, Result<String>> + Send + Sync,
+{
+    fn solve(&self, image: Vec<u8>, uuid: String) -> BoxFuture<'_, Result<String>> {
+        self(image, uuid)
+    }
+}
+
+/// Saves the image and reads the answer from stdin
+///
+/// Solvers made with [`StdinSolver::for_account`] share one prompt, so only
+/// one captcha waits for input at a time.
+#[derive(Debug, Clone)]
+pub struct StdinSolver {
+    pub image_path: PathBuf,
+    /// Shown before the prompt
+    pub account: Option<String>,
+    /// Held while prompting
+    prompt: Arc<Mutex<()>>,
+}
+
+impl StdinSolver {
+    pub fn new(image_path: impl Into<PathBuf>) -> Self {
+        Self {
+            image_path: image_path.into(),
+            account: None,
+            prompt: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Solver for `account` saving to `captcha-<account>.png` next to this
+    /// image and sharing its prompt
+    pub fn for_account(&self, account: &str) -> Self {
+        // 用户名只用于文件名，去掉路径分隔符等字符
+        let name: String = account
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() || c == '-' {
+                    c
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        Self {
+            image_path: self
+                .image_path
+                .with_file_name(format!("captcha-{name}.png")),
+            account: Some(account.to_string()),
+            prompt: Arc::clone(&self.prompt),
+        }
+    }
+}
+
+impl Default for StdinSolver {
+    fn default() -> Self {
+        Self::new("captcha.png")
+    }
+}
+
+impl CaptchaSolver for StdinSolver {
+    fn solve(&self, image: Vec<u8>, _uuid: String) -> BoxFuture<'_, Result<String>> {
+        let image_path = self.image_path.clone();
+        let prefix = match &self.account {
+            Some(account) => format!("[{account}] "),
+            None => String::new(),
+        };
+        Box::pin(async move {
+            let _prompt = self.prompt.lock().await;
+            tokio::task::spawn_blocking(move || -> Result<String> {
+                std::fs::write(&image_path, image)?;
+
+                eprintln!(
+                    "{prefix}Please check {} and enter the captcha:",
+                    image_path.display()
+                );
+                let mut captcha = String::new();
+                std::io::stdin().read_line(&mut captcha)?;
+                Ok(captcha.trim().to_string())
+            })
+            .await
+            .map_err(|e| ErrorKind::StdIoError(std::io::Error::other(e)))?
+        })
+    }
+}
+
+/// Writes the image into a directory and waits for an answer file next to it
+///
+/// For a captcha `uuid` the image is `<dir>/<uuid>.png` and the answer is read
+/// from `<dir>/<uuid>.txt`. Both files are removed once answered. The uuid
+/// comes from the server, so anything but `[A-Za-z0-9-]` is rejected.
+#[derive(Debug, Clone)]
+pub struct FileDropSolver {
+    pub dir: PathBuf,
+    pub poll_interval: Duration,
+    pub timeout: Duration,
+}
+
+impl FileDropSolver {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            poll_interval: Duration::from_millis(200),
+            timeout: Duration::from_secs(300),
+        }
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn image_path(&self, uuid: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(format!("{}.png", checked_uuid(uuid)?)))
+    }
+
+    pub fn answer_path(&self, uuid: &str) -> Result<PathBuf> {
+        Ok(self.dir.join(format!("{}.txt", checked_uuid(uuid)?)))
+    }
+}
+
+impl CaptchaSolver for FileDropSolver {
+    fn solve(&self, image: Vec<u8>, uuid: String) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move {
+            let image_path = self.image_path(&uuid)?;
+            let answer_path = self.answer_path(&uuid)?;
+            std::fs::create_dir_all(&self.dir)?;
+            std::fs::write(&image_path, image)?;
+
+            let wait = async {
+                loop {
+                    // 等待答案文件写入完成（非空）
+                    if let Ok(answer) = std::fs::read_to_string(&answer_path)
+                        && !answer.trim().is_empty()
+                    {
+                        return answer.trim().to_string();
+                    }
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            };
+            let answer = tokio::time::timeout(self.timeout, wait).await;
+
+            let _ = std::fs::remove_file(&image_path);
+            let _ = std::fs::remove_file(&answer_path);
+            answer.map_err(|_| {
+                ErrorKind::CaptchaUnsolved(format!(
+                    "no answer in {} after {:?}",
+                    answer_path.display(),
+                    self.timeout
+                ))
+                .into()
+            })
+        })
+    }
+}
+
+/// `uuid` if it is safe to use as a file name, so it cannot leave the drop
+/// directory
+fn checked_uuid(uuid: &str) -> Result<&str> {
+    if !uuid.is_empty() && uuid.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        Ok(uuid)
+    } else {
+        Err(ErrorKind::ParseError(format!("Invalid captcha uuid {uuid:?}")).into())
+    }
+}
+
+/// Captcha waiting for an answer from a [`ChannelSolver`] consumer
+#[derive(Debug)]
+pub struct CaptchaRequest {
+    pub uuid: String,
+    pub image: Vec<u8>,
+    reply: oneshot::Sender<String>,
+}
+
+impl CaptchaRequest {
+    /// Send the answer back to the waiting login
+    pub fn respond(self, answer: impl Into<String>) {
+        let _ = self.reply.send(answer.into());
+    }
+}
+
+/// Forwards captchas to a receiver, e.g. a GUI event loop, and awaits the reply
+#[derive(Debug, Clone)]
+pub struct ChannelSolver {
+    sender: mpsc::Sender<CaptchaRequest>,
+}
+
+impl ChannelSolver {
+    /// Solver and the receiving end the UI should drain
+    pub fn new() -> (Self, mpsc::Receiver<CaptchaRequest>) {
+        let (sender, receiver) = mpsc::channel(1);
+        (Self { sender }, receiver)
+    }
+}
+
+impl CaptchaSolver for ChannelSolver {
+    fn solve(&self, image: Vec<u8>, uuid: String) -> BoxFuture<'_, Result<String>> {
+        Box::pin(async move {
+            let (reply, answer) = oneshot::channel();
+            self.sender
+                .send(CaptchaRequest { uuid, image, reply })
+                .await
+                .map_err(|_| ErrorKind::CaptchaUnsolved("captcha receiver closed".to_string()))?;
+            answer.await.map_err(|_| {
+                ErrorKind::CaptchaUnsolved("captcha request dropped".to_string()).into()
+            })
+        })
+    }
+}
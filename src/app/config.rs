@@ -0,0 +1,355 @@
+//! Configuration file of the command line client
+//!
+//! [`AppConfig`] is read from TOML, or from JSON when the file ends in
+//! `.json`. Every field is optional so command line flags can fill in or
+//! override whatever the file leaves out:
+//!
+//! ```toml
+//! username = "2023000000"
+//! password = { env = "FUNKY_PASSWORD" }
+//! # 或者不写 password，从 `vault add` 创建的加密保险库读取
+//! # vault = "funky_vault.json"
+//! batch = { name = "2025-2026学年第一学期选课" }
+//! courses = ["高等数学", "2025-2026-1-0001", { name = "大学物理", teacher = "李老师" }]
+//! # 同一时间段的备选课程，按优先级排列，选上一门后不再尝试其余课程
+//! groups = [["class-a", "class-b", { name = "体育", teacher = "李老师" }]]
+//! loop = true
+//!
+//! [enroll]
+//! workers = 4
+//! interval_ms = 200
+//!
+//! # 同时为多个账号选课，未填写的 batch 沿用上面的设置
+//! [[accounts]]
+//! username = "2023000001"
+//! courses = ["大学物理"]
+//! ```
+
+use crate::client::config::ServerConfig;
+use crate::client::rate_limit::{Endpoint, RateLimit, RateLimiter};
+use crate::error::{ErrorKind, Result};
+use crate::model::structs::{BatchSelector, CourseInfo, CourseTarget};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use super::engine::{CourseGroup, EnrollConfig};
+use super::reconcile::{SameCourse, reconcile};
+use super::scheduler::ScheduleConfig;
+
+/// Where to read the password from, so it does not have to live in the file
+#[derive(Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum PasswordSource {
+    Plain(String),
+    Env {
+        env: String,
+    },
+    /// First line of a file
+    File {
+        file: PathBuf,
+    },
+}
+
+impl PasswordSource {
+    pub fn resolve(&self) -> Result<String> {
+        match self {
+            Self::Plain(password) => Ok(password.clone()),
+            Self::Env { env } => std::env::var(env)
+                .map_err(|_| ErrorKind::ParseError(format!("环境变量 {env} 未设置")).into()),
+            Self::File { file } => {
+                let content = std::fs::read_to_string(file)?;
+                Ok(content.lines().next().unwrap_or_default().to_string())
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for PasswordSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Plain(_) => f.write_str("Plain(***)"),
+            Self::Env { env } => f.debug_struct("Env").field("env", env).finish(),
+            Self::File { file } => f.debug_struct("File").field("file", file).finish(),
+        }
+    }
+}
+
+/// Worker and pacing settings, see [`EnrollConfig`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EnrollSection {
+    pub workers: Option<usize>,
+    pub interval_ms: Option<u64>,
+    pub jitter_ms: Option<u64>,
+    /// Same as `rate_limit.addxk_rps`, which takes precedence, see
+    /// [`AppConfig::rate_limiter`]
+    pub max_rps: Option<f64>,
+    pub backoff_ms: Option<u64>,
+    pub backoff_max_ms: Option<u64>,
+    pub try_if_capacity_full: Option<bool>,
+    /// Favorite refreshes per course on `参数校验不通过`
+    pub param_refreshes: Option<u32>,
+    /// Check the selected list after a success or a transport error
+    pub verify: Option<bool>,
+    /// Pause between two rounds in loop mode
+    pub round_interval_ms: Option<u64>,
+}
+
+impl EnrollSection {
+    /// Override the fields of `config` that are set here
+    pub fn apply(&self, config: &mut EnrollConfig) {
+        if let Some(workers) = self.workers {
+            config.workers = workers;
+        }
+        if let Some(ms) = self.interval_ms {
+            config.interval = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.jitter_ms {
+            config.jitter = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.backoff_ms {
+            config.backoff.initial = Duration::from_millis(ms);
+        }
+        if let Some(ms) = self.backoff_max_ms {
+            config.backoff.max = Duration::from_millis(ms);
+        }
+        if let Some(try_if_capacity_full) = self.try_if_capacity_full {
+            config.try_if_capacity_full = try_if_capacity_full;
+        }
+        if let Some(param_refreshes) = self.param_refreshes {
+            config.param_refreshes = param_refreshes;
+        }
+        if let Some(verify) = self.verify {
+            config.verify = verify;
+        }
+    }
+}
+
+/// Request rate limits shared by the whole client
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RateLimitSection {
+    /// All endpoints together, 0 for no limit
+    pub global_rps: Option<f64>,
+    pub global_burst: Option<u32>,
+    /// `addxk` alone, summed over all workers and accounts
+    pub addxk_rps: Option<f64>,
+}
+
+impl RateLimitSection {
+    pub fn rate_limiter(&self) -> RateLimiter {
+        let global = RateLimit::per_second(self.global_rps.unwrap_or(10.0))
+            .with_burst(self.global_burst.unwrap_or(4));
+        let limiter = RateLimiter::new(Some(global));
+        match self.addxk_rps {
+            Some(rps) => limiter.with_endpoint(Endpoint::AddCourse, RateLimit::per_second(rps)),
+            None => limiter,
+        }
+    }
+}
+
+/// Waiting for the batch to open, see [`ScheduleConfig`]
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScheduleSection {
+    pub enabled: Option<bool>,
+    pub lead_ms: Option<u64>,
+    pub warm_up_secs: Option<u64>,
+    /// Requests used to estimate the server clock, 0 to trust the local clock
+    pub clock_samples: Option<usize>,
+}
+
+impl ScheduleSection {
+    /// Schedule settings, `None` unless scheduling is enabled
+    pub fn schedule_config(&self) -> Option<ScheduleConfig> {
+        if !self.enabled.unwrap_or(false) {
+            return None;
+        }
+        let mut config = ScheduleConfig::default();
+        if let Some(ms) = self.lead_ms {
+            config.lead_time = Duration::from_millis(ms);
+        }
+        if let Some(secs) = self.warm_up_secs {
+            config.warm_up = Duration::from_secs(secs);
+        }
+        Some(config)
+    }
+}
+
+/// One account of a multi-account run, see [`AccountPool`](super::pool::AccountPool)
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccountSection {
+    pub username: String,
+    /// Looked up in the vault when not set
+    #[serde(default)]
+    pub password: Option<PasswordSource>,
+    /// Falls back to the top level `batch`
+    #[serde(default)]
+    pub batch: Option<BatchSelector>,
+    /// Like [`AppConfig::courses`]
+    #[serde(default)]
+    pub courses: Vec<CourseTarget>,
+    /// Like [`AppConfig::groups`]
+    #[serde(default)]
+    pub groups: Vec<Vec<CourseTarget>>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct AppConfig {
+    pub username: Option<String>,
+    pub password: Option<PasswordSource>,
+    /// Credential vault to look the password up in when `password` is not set
+    pub vault: Option<PathBuf>,
+    pub batch: Option<BatchSelector>,
+    /// Courses to enroll in, see [`resolve_targets`]
+    pub courses: Vec<CourseTarget>,
+    /// Groups of alternatives, see [`resolve_targets`]
+    pub groups: Vec<Vec<CourseTarget>>,
+    /// Keep enrolling round after round
+    #[serde(rename = "loop")]
+    pub loop_mode: Option<bool>,
+    pub enroll: EnrollSection,
+    pub rate_limit: RateLimitSection,
+    pub schedule: ScheduleSection,
+    /// Server address and endpoints, the official server by default
+    pub server: Option<ServerConfig>,
+    /// Accounts enrolled side by side instead of `username`
+    pub accounts: Vec<AccountSection>,
+}
+
+impl AppConfig {
+    /// Read a TOML file, or JSON if the extension is `.json`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)?;
+        let is_json = path
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        if is_json {
+            Self::from_json(&content)
+        } else {
+            Self::from_toml(&content)
+        }
+    }
+
+    pub fn from_toml(content: &str) -> Result<Self> {
+        toml::from_str(content)
+            .map_err(|e| ErrorKind::ParseError(format!("Invalid config: {e}")).into())
+    }
+
+    pub fn from_json(content: &str) -> Result<Self> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Limiter shared by every client, with `enroll.max_rps` as the `addxk`
+    /// limit unless `rate_limit.addxk_rps` is set
+    ///
+    /// The limit applies to all workers and accounts together, so
+    /// [`EnrollConfig::max_rps`] is left unset.
+    pub fn rate_limiter(&self) -> RateLimiter {
+        RateLimitSection {
+            addxk_rps: self.rate_limit.addxk_rps.or(self.enroll.max_rps),
+            ..self.rate_limit.clone()
+        }
+        .rate_limiter()
+    }
+
+    pub fn enroll_config(&self) -> EnrollConfig {
+        let mut config = EnrollConfig::default();
+        self.enroll.apply(&mut config);
+        config
+    }
+
+    /// Match `courses` and `groups` against the favorites and the selected courses
+    pub fn target_courses(
+        &self,
+        favorites: &[CourseInfo],
+        selected: &[CourseInfo],
+    ) -> TargetResolution {
+        resolve_targets(&self.courses, &self.groups, favorites, selected)
+    }
+}
+
+/// Courses picked by a list of [`CourseTarget`]s
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TargetResolution {
+    /// Matching favorites that still have to be selected
+    pub courses: Vec<CourseInfo>,
+    /// Matching courses that are selected already
+    pub already_selected: Vec<CourseInfo>,
+    /// Matching favorites left out because another class of the same
+    /// course is selected
+    #[serde(default)]
+    pub same_course_selected: Vec<SameCourse>,
+    /// Groups none of whose courses is selected yet, for [`EnrollConfig::groups`]
+    pub groups: Vec<CourseGroup>,
+    /// Targets matching neither a favorite nor a selected course
+    pub unresolved: Vec<CourseTarget>,
+}
+
+/// Resolve `targets` and `groups` against the favorites, all favorites if
+/// both are empty
+///
+/// Each group lists alternatives best first, of which at most one should
+/// be selected, and becomes a [`CourseGroup`].
+///
+/// Only favorites carry the `secretVal` needed to enroll, so a course has to
+/// be favorited on the website to be enrolled in. Favorites are
+/// [`reconcile`]d with the selected courses first, and a group with a
+/// selected course is satisfied and its other courses are left out.
+pub fn resolve_targets(
+    targets: &[CourseTarget],
+    groups: &[Vec<CourseTarget>],
+    favorites: &[CourseInfo],
+    selected: &[CourseInfo],
+) -> TargetResolution {
+    let reconciliation = reconcile(favorites, selected);
+    let all_targets = || targets.iter().chain(groups.iter().flatten());
+    let wanted = |course: &CourseInfo| {
+        targets.is_empty() && groups.is_empty() || all_targets().any(|t| t.matches(course))
+    };
+
+    let mut resolution = TargetResolution::default();
+    let mut satisfied = Vec::new();
+    for group in groups {
+        // 按目标顺序排列，越靠前优先级越高
+        let mut class_ids: Vec<String> = Vec::new();
+        for course in group
+            .iter()
+            .flat_map(|t| favorites.iter().filter(|c| t.matches(c)))
+        {
+            if !class_ids.contains(&course.JXBID) {
+                class_ids.push(course.JXBID.clone());
+            }
+        }
+        // 组内课程或同一课程的其他教学班已选上
+        if selected.iter().any(|c| group.iter().any(|t| t.matches(c)))
+            || class_ids.iter().any(|id| reconciliation.is_skipped(id))
+        {
+            satisfied.extend(class_ids);
+        } else if !class_ids.is_empty() {
+            resolution.groups.push(CourseGroup { class_ids });
+        }
+    }
+
+    resolution.courses = reconciliation
+        .to_enroll
+        .iter()
+        .filter(|c| wanted(c) && !satisfied.contains(&c.JXBID))
+        .cloned()
+        .collect();
+    resolution.already_selected = selected.iter().filter(|c| wanted(c)).cloned().collect();
+    resolution.same_course_selected = reconciliation
+        .same_course_selected
+        .into_iter()
+        .filter(|s| wanted(&s.favorite))
+        .collect();
+    resolution.unresolved = all_targets()
+        .filter(|t| !favorites.iter().chain(selected).any(|c| t.matches(c)))
+        .cloned()
+        .collect();
+    resolution
+}
@@ -0,0 +1,716 @@
+//! Enrollment engine shared by the TUI and GUI front ends
+//!
+//! [`EnrollmentEngine`] runs the worker pool against a [`SessionManager`] and
+//! reports progress as [`EnrollEvent`]s. Front ends subscribe to the events
+//! and only render them, so enrollment behaviour lives in one place.
+
+use crate::client::rate_limit::{Endpoint, RateLimit, RateLimiter};
+use crate::error::Result;
+use crate::model::structs::{CourseInfo, CourseOutcome, EnrollOutcome};
+use futures::future::{Either, join_all};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex as StdMutex};
+use std::time::Duration;
+use tokio::sync::Mutex as TokioMutex;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::events::Subscribers;
+use super::session::SessionManager;
+
+const WORK_THREAD_COUNT: usize = 4;
+const PARAM_REFRESHES: u32 = 3;
+
+/// How workers walk the course list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Strategy {
+    /// Each worker visits every course once, retrying it until it is finished
+    #[default]
+    SinglePass,
+    /// Workers round robin over the unfinished courses until all are
+    /// finished or the run is cancelled
+    Continuous,
+}
+
+/// Exponential backoff applied after consecutive failed requests
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Backoff {
+    pub initial: Duration,
+    pub max: Duration,
+    pub multiplier: f64,
+}
+
+impl Backoff {
+    /// Delay after the `failures`-th consecutive failure, starting at 1
+    pub fn delay(&self, failures: u32) -> Duration {
+        let exponent = failures.saturating_sub(1).min(32) as i32;
+        let delay = self.initial.as_secs_f64() * self.multiplier.max(1.0).powi(exponent);
+        Duration::from_secs_f64(delay.min(self.max.as_secs_f64()))
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self {
+            initial: Duration::from_millis(500),
+            max: Duration::from_secs(8),
+            multiplier: 2.0,
+        }
+    }
+}
+
+/// Alternatives for the same slot, best first; at most one gets selected
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CourseGroup {
+    pub class_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnrollConfig {
+    pub strategy: Strategy,
+    pub workers: usize,
+    /// Keep retrying a course whose capacity is full
+    pub try_if_capacity_full: bool,
+    /// Pause between two requests of the same worker
+    pub interval: Duration,
+    /// Random extra pause of up to `jitter` added to `interval`
+    pub jitter: Duration,
+    /// Requests per second summed over all workers of this run, `None` for
+    /// no limit
+    ///
+    /// A limit shared with other runs or accounts belongs in the client's
+    /// [`RateLimiter`] instead.
+    pub max_rps: Option<f64>,
+    /// Delay after transport errors and unknown server failures
+    pub backoff: Backoff,
+    /// Courses of a group are requested one at a time in priority order and
+    /// the rest are dropped once one is selected
+    pub groups: Vec<CourseGroup>,
+    /// Times to re-fetch the favorites of a course rejected with
+    /// `参数校验不通过` before giving up on it
+    pub param_refreshes: u32,
+    /// Check the selected list after a success or a transport error, since
+    /// the server may have committed a request whose reply was lost
+    pub verify: bool,
+}
+
+impl EnrollConfig {
+    /// Pause before the next request of a worker
+    fn pause(&self) -> Duration {
+        let jitter = self.jitter.as_millis() as u64;
+        if jitter == 0 {
+            return self.interval;
+        }
+        self.interval + Duration::from_millis(fastrand::u64(0..=jitter))
+    }
+}
+
+impl Default for EnrollConfig {
+    fn default() -> Self {
+        Self {
+            strategy: Strategy::default(),
+            workers: WORK_THREAD_COUNT,
+            try_if_capacity_full: true,
+            interval: Duration::ZERO,
+            jitter: Duration::ZERO,
+            max_rps: None,
+            backoff: Backoff::default(),
+            groups: Vec::new(),
+            param_refreshes: PARAM_REFRESHES,
+            verify: true,
+        }
+    }
+}
+
+/// Progress reported by [`EnrollmentEngine::run`], and by a
+/// [`Scheduler`](super::scheduler::Scheduler) waiting to run it
+#[derive(Debug, Clone)]
+pub enum EnrollEvent {
+    /// The batch opens soon and the session is being refreshed
+    WarmingUp,
+    /// The scheduled run starts at `server_time` (Asia/Shanghai)
+    Starting { server_time: String },
+    /// The batch closed and the scheduler stopped the run
+    BatchEnded,
+    /// An `addxk` request for a course returned
+    Attempt {
+        worker: usize,
+        class_id: String,
+        name: String,
+        outcome: EnrollOutcome,
+        total_requests: u32,
+    },
+    /// The selected list was checked for a course after a success or a
+    /// transport error
+    Verified {
+        class_id: String,
+        name: String,
+        confirmed: bool,
+    },
+    /// A course reached a state where retrying is pointless
+    CourseFinished {
+        class_id: String,
+        name: String,
+        outcome: EnrollOutcome,
+    },
+    /// The run is over, always the last event
+    Finished(EnrollReport),
+}
+
+/// Final outcome of every course in a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnrollReport {
+    pub total_requests: u32,
+    pub courses: Vec<CourseOutcome>,
+}
+
+impl EnrollReport {
+    /// Every course reached a final outcome
+    pub fn is_complete(&self) -> bool {
+        self.courses.iter().all(|c| c.finished)
+    }
+}
+
+/// A [`CourseGroup`] restricted to the courses of a run
+struct GroupState {
+    class_ids: Vec<String>,
+    /// Position of the next alternative to request; held while requesting
+    next: TokioMutex<usize>,
+}
+
+/// State shared by the workers of one run
+struct RunState {
+    courses: Vec<CourseInfo>,
+    outcomes: StdMutex<HashMap<String, EnrollOutcome>>,
+    total_requests: AtomicU32,
+    /// Enforces `max_rps` for this run
+    limiter: Option<RateLimiter>,
+    groups: Vec<GroupState>,
+    /// Index into `groups` by JXBID
+    group_of: HashMap<String, usize>,
+    /// Courses with a `secretVal` newer than the one passed to `run`
+    refreshed: StdMutex<HashMap<String, CourseInfo>>,
+    /// Favorite refreshes per course
+    refreshes: StdMutex<HashMap<String, u32>>,
+    /// Held while re-fetching the favorites
+    refresh_lock: TokioMutex<()>,
+    /// Courses given up after too many refreshes
+    abandoned: StdMutex<HashSet<String>>,
+    /// Result of the latest selected list check per course
+    confirmed: StdMutex<HashMap<String, bool>>,
+    /// Held while checking the selected list
+    verify_lock: TokioMutex<()>,
+}
+
+impl RunState {
+    fn new(courses: &[CourseInfo], config: &EnrollConfig) -> Self {
+        let mut groups = Vec::new();
+        let mut group_of = HashMap::new();
+        for group in &config.groups {
+            // 只保留本轮要选的课程，一门课只属于第一个包含它的组
+            let class_ids: Vec<String> = group
+                .class_ids
+                .iter()
+                .filter(|id| courses.iter().any(|c| c.JXBID == **id))
+                .filter(|id| !group_of.contains_key(*id))
+                .cloned()
+                .collect();
+            if class_ids.is_empty() {
+                continue;
+            }
+            for id in &class_ids {
+                group_of.insert(id.clone(), groups.len());
+            }
+            groups.push(GroupState {
+                class_ids,
+                next: TokioMutex::new(0),
+            });
+        }
+
+        Self {
+            courses: courses.to_vec(),
+            outcomes: StdMutex::new(HashMap::new()),
+            total_requests: AtomicU32::new(0),
+            limiter: config
+                .max_rps
+                .filter(|rps| *rps > 0.0)
+                .map(|rps| RateLimiter::new(Some(RateLimit::per_second(rps)))),
+            groups,
+            group_of,
+            refreshed: StdMutex::new(HashMap::new()),
+            refreshes: StdMutex::new(HashMap::new()),
+            refresh_lock: TokioMutex::new(()),
+            abandoned: StdMutex::new(HashSet::new()),
+            confirmed: StdMutex::new(HashMap::new()),
+            verify_lock: TokioMutex::new(()),
+        }
+    }
+
+    /// `course` with the latest `secretVal` and `teachingClassType`
+    fn current(&self, course: &CourseInfo) -> CourseInfo {
+        self.refreshed
+            .lock()
+            .unwrap()
+            .get(&course.JXBID)
+            .cloned()
+            .unwrap_or_else(|| course.clone())
+    }
+
+    fn course(&self, class_id: &str) -> Option<&CourseInfo> {
+        self.courses.iter().find(|c| c.JXBID == class_id)
+    }
+
+    fn report(&self, is_finished: impl Fn(&str) -> bool) -> EnrollReport {
+        // `is_finished` locks `outcomes` itself
+        let finished: Vec<bool> = self.courses.iter().map(|c| is_finished(&c.JXBID)).collect();
+        let outcomes = self.outcomes.lock().unwrap();
+        let confirmed = self.confirmed.lock().unwrap();
+        EnrollReport {
+            total_requests: self.total_requests.load(Ordering::SeqCst),
+            courses: self
+                .courses
+                .iter()
+                .zip(finished)
+                .map(|(c, finished)| CourseOutcome {
+                    class_id: c.JXBID.clone(),
+                    name: c.KCM.clone(),
+                    outcome: outcomes.get(&c.JXBID).cloned(),
+                    confirmed: confirmed.get(&c.JXBID).copied(),
+                    finished,
+                })
+                .collect(),
+        }
+    }
+}
+
+pub struct EnrollmentEngine {
+    session: Arc<SessionManager>,
+    config: EnrollConfig,
+    cancel: CancellationToken,
+    subscribers: Subscribers<EnrollEvent>,
+}
+
+impl EnrollmentEngine {
+    pub fn new(session: Arc<SessionManager>, config: EnrollConfig) -> Self {
+        Self {
+            session,
+            config,
+            cancel: CancellationToken::new(),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Stop the run once `cancel` is cancelled, interrupting pending
+    /// requests and pauses
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn config(&self) -> &EnrollConfig {
+        &self.config
+    }
+
+    /// Token that stops the run when cancelled
+    pub fn cancellation(&self) -> &CancellationToken {
+        &self.cancel
+    }
+
+    /// Receive the events of subsequent runs
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<EnrollEvent> {
+        self.subscribers.subscribe()
+    }
+
+    /// Enroll in `courses` until the strategy is exhausted or the run is stopped
+    pub async fn run(&self, courses: &[CourseInfo]) -> Result<EnrollReport> {
+        let state = RunState::new(courses, &self.config);
+
+        if !courses.is_empty() {
+            let workers =
+                (0..self.config.workers.max(1)).map(|worker| match self.config.strategy {
+                    Strategy::SinglePass => Either::Left(self.single_pass(&state, worker)),
+                    Strategy::Continuous => Either::Right(self.continuous(&state, worker)),
+                });
+            join_all(workers).await;
+        }
+
+        let report = state.report(|class_id| self.is_finished(&state, class_id));
+        self.emit(EnrollEvent::Finished(report.clone()));
+        Ok(report)
+    }
+
+    async fn single_pass(&self, state: &RunState, worker: usize) {
+        let course_count = state.courses.len();
+        let mut failures = 0;
+        // 从不同位置开始遍历课程
+        for offset in 0..course_count {
+            let course = &state.courses[(worker + offset) % course_count];
+            while self.is_running() && !self.is_finished(state, &course.JXBID) {
+                let Some(outcome) = self
+                    .cancel
+                    .run_until_cancelled(self.attempt(state, worker, course))
+                    .await
+                else {
+                    return;
+                };
+                if let Some(outcome) = outcome
+                    && !self.is_finished(state, &course.JXBID)
+                {
+                    self.pace(&outcome, &mut failures).await;
+                }
+            }
+        }
+    }
+
+    async fn continuous(&self, state: &RunState, worker: usize) {
+        let course_count = state.courses.len();
+        let mut course_idx = worker % course_count;
+        let mut failures = 0;
+        // 所有课程都有最终结果后自动结束
+        while self.is_running()
+            && !state
+                .courses
+                .iter()
+                .all(|c| self.is_finished(state, &c.JXBID))
+        {
+            let course = &state.courses[course_idx];
+            course_idx = (course_idx + 1) % course_count;
+            if self.is_finished(state, &course.JXBID) {
+                continue;
+            }
+            let Some(outcome) = self
+                .cancel
+                .run_until_cancelled(self.attempt(state, worker, course))
+                .await
+            else {
+                break;
+            };
+
+            match outcome {
+                // 短暂延迟避免请求过快
+                Some(outcome) => self.pace(&outcome, &mut failures).await,
+                // 同组已有课程选上，没有发出请求
+                None => self.sleep(self.config.pause()).await,
+            }
+        }
+    }
+
+    /// Wait before the next request, backing off after consecutive failures
+    async fn pace(&self, outcome: &EnrollOutcome, failures: &mut u32) {
+        let delay = match outcome {
+            EnrollOutcome::Transport { .. }
+            | EnrollOutcome::Unknown { .. }
+            | EnrollOutcome::ParamInvalid => {
+                *failures += 1;
+                self.config
+                    .backoff
+                    .delay(*failures)
+                    .max(self.config.pause())
+            }
+            _ => {
+                *failures = 0;
+                self.config.pause()
+            }
+        };
+        if !delay.is_zero() {
+            self.sleep(delay).await;
+        }
+    }
+
+    /// Sleep for `duration` unless the run is cancelled first
+    async fn sleep(&self, duration: Duration) {
+        self.cancel
+            .run_until_cancelled(tokio::time::sleep(duration))
+            .await;
+    }
+
+    /// Wait for a request slot under `max_rps`
+    async fn throttle(&self, state: &RunState) {
+        if let Some(limiter) = &state.limiter {
+            limiter.acquire(Endpoint::AddCourse).await;
+        }
+    }
+
+    /// Request `course`, or the next alternative of its group
+    ///
+    /// Returns `None` if nothing was requested because every alternative of
+    /// the group is finished.
+    async fn attempt(
+        &self,
+        state: &RunState,
+        worker: usize,
+        course: &CourseInfo,
+    ) -> Option<EnrollOutcome> {
+        let Some(&group_idx) = state.group_of.get(&course.JXBID) else {
+            return Some(self.send(state, worker, course).await);
+        };
+        let group = &state.groups[group_idx];
+
+        // 同组课程互斥，同一时间只发一个请求，避免同时选上两门
+        let mut next = group.next.lock().await;
+        let count = group.class_ids.len();
+        let idx = (0..count)
+            .map(|offset| (*next + offset) % count)
+            .find(|idx| !self.is_finished(state, &group.class_ids[*idx]))?;
+        let candidate = state.course(&group.class_ids[idx])?;
+        let outcome = self.send(state, worker, candidate).await;
+
+        if outcome.is_selected() {
+            self.finish_group(state, group, candidate);
+        } else {
+            // 依次尝试下一个备选，一轮结束后回到优先级最高的课程
+            *next = (idx + 1) % count;
+        }
+        Some(outcome)
+    }
+
+    /// Mark the other alternatives of `group` finished once `selected` is in
+    fn finish_group(&self, state: &RunState, group: &GroupState, selected: &CourseInfo) {
+        for class_id in group.class_ids.iter().filter(|id| **id != selected.JXBID) {
+            let outcome = EnrollOutcome::AlternativeSelected {
+                class_id: selected.JXBID.clone(),
+            };
+            {
+                let mut outcomes = state.outcomes.lock().unwrap();
+                if outcomes.get(class_id).is_some_and(|o| self.is_terminal(o)) {
+                    continue;
+                }
+                outcomes.insert(class_id.clone(), outcome.clone());
+            }
+            let name = state
+                .course(class_id)
+                .map(|c| c.KCM.clone())
+                .unwrap_or_default();
+            self.emit(EnrollEvent::CourseFinished {
+                class_id: class_id.clone(),
+                name,
+                outcome,
+            });
+        }
+    }
+
+    /// Send one `addxk` request and record its outcome
+    async fn send(&self, state: &RunState, worker: usize, course: &CourseInfo) -> EnrollOutcome {
+        let course = &state.current(course);
+        self.throttle(state).await;
+        let result = self.session.select_course(course).await;
+        let outcome = EnrollOutcome::classify(&result);
+        let total_requests = state.total_requests.fetch_add(1, Ordering::SeqCst) + 1;
+
+        {
+            let mut outcomes = state.outcomes.lock().unwrap();
+            // 其他工作线程已经完成该课程，忽略本次结果
+            if outcomes
+                .get(&course.JXBID)
+                .is_some_and(|o| self.is_terminal(o))
+            {
+                return outcome;
+            }
+            outcomes.insert(course.JXBID.clone(), outcome.clone());
+        }
+
+        self.emit(EnrollEvent::Attempt {
+            worker,
+            class_id: course.JXBID.clone(),
+            name: course.KCM.clone(),
+            outcome: outcome.clone(),
+            total_requests,
+        });
+        if self.is_terminal(&outcome) {
+            self.emit(EnrollEvent::CourseFinished {
+                class_id: course.JXBID.clone(),
+                name: course.KCM.clone(),
+                outcome: outcome.clone(),
+            });
+        }
+        match outcome {
+            EnrollOutcome::ParamInvalid => self.refresh_course(state, course).await,
+            EnrollOutcome::Success | EnrollOutcome::Transport { .. } if self.config.verify => {
+                return self.verify(state, course, outcome).await;
+            }
+            _ => {}
+        }
+        outcome
+    }
+
+    /// Check the selected list after `outcome` of `sent`
+    ///
+    /// Every course of the run found there is finished, so no worker keeps
+    /// requesting a course the server already committed. Returns `Success`
+    /// if `sent` is listed and `outcome` otherwise.
+    async fn verify(
+        &self,
+        state: &RunState,
+        sent: &CourseInfo,
+        outcome: EnrollOutcome,
+    ) -> EnrollOutcome {
+        let _guard = state.verify_lock.lock().await;
+        // 获取失败时无法确认，保持原结果
+        let Ok(selected) = self.session.get_selected_courses().await else {
+            return outcome;
+        };
+        let listed = |class_id: &str| selected.iter().any(|c| c.JXBID == class_id);
+
+        for course in state.courses.iter().filter(|c| listed(&c.JXBID)) {
+            let previous = state
+                .confirmed
+                .lock()
+                .unwrap()
+                .insert(course.JXBID.clone(), true);
+            if previous == Some(true) {
+                continue;
+            }
+            self.emit(EnrollEvent::Verified {
+                class_id: course.JXBID.clone(),
+                name: course.KCM.clone(),
+                confirmed: true,
+            });
+
+            let finished = if course.JXBID == sent.JXBID {
+                EnrollOutcome::Success
+            } else {
+                EnrollOutcome::AlreadySelected
+            };
+            let newly_finished = {
+                let mut outcomes = state.outcomes.lock().unwrap();
+                if outcomes
+                    .get(&course.JXBID)
+                    .is_some_and(|o| self.is_terminal(o))
+                {
+                    false
+                } else {
+                    outcomes.insert(course.JXBID.clone(), finished.clone());
+                    true
+                }
+            };
+            if newly_finished {
+                self.emit(EnrollEvent::CourseFinished {
+                    class_id: course.JXBID.clone(),
+                    name: course.KCM.clone(),
+                    outcome: finished,
+                });
+            }
+            if let Some(&group_idx) = state.group_of.get(&course.JXBID) {
+                self.finish_group(state, &state.groups[group_idx], course);
+            }
+        }
+
+        if listed(&sent.JXBID) {
+            return EnrollOutcome::Success;
+        }
+        // 返回成功但选课结果中没有该课程
+        if outcome == EnrollOutcome::Success {
+            state
+                .confirmed
+                .lock()
+                .unwrap()
+                .insert(sent.JXBID.clone(), false);
+            self.emit(EnrollEvent::Verified {
+                class_id: sent.JXBID.clone(),
+                name: sent.KCM.clone(),
+                confirmed: false,
+            });
+        }
+        outcome
+    }
+
+    /// Re-fetch the favorites after `sent` failed with `参数校验不通过`
+    ///
+    /// Usually its `secretVal` is stale. Gives up on the course after
+    /// `param_refreshes` refreshes, or at once if it left the favorites.
+    async fn refresh_course(&self, state: &RunState, sent: &CourseInfo) {
+        let _guard = state.refresh_lock.lock().await;
+        let current = state.current(sent);
+        // 其他工作线程已经刷新过参数
+        if current.secret_val != sent.secret_val
+            || current.teaching_class_type != sent.teaching_class_type
+        {
+            return;
+        }
+
+        let refreshes = {
+            let mut refreshes = state.refreshes.lock().unwrap();
+            let count = refreshes.entry(sent.JXBID.clone()).or_default();
+            *count += 1;
+            *count
+        };
+        if refreshes > self.config.param_refreshes {
+            state.abandoned.lock().unwrap().insert(sent.JXBID.clone());
+            self.emit(EnrollEvent::CourseFinished {
+                class_id: sent.JXBID.clone(),
+                name: sent.KCM.clone(),
+                outcome: EnrollOutcome::ParamInvalid,
+            });
+            return;
+        }
+
+        // 获取失败时等下次参数错误再刷新
+        let Ok(favorites) = self.session.get_favorite_courses().await else {
+            return;
+        };
+        match favorites.into_iter().find(|c| c.JXBID == sent.JXBID) {
+            Some(fresh) => {
+                let fresh = CourseInfo {
+                    secret_val: fresh.secret_val,
+                    teaching_class_type: fresh.teaching_class_type,
+                    ..current
+                };
+                state
+                    .refreshed
+                    .lock()
+                    .unwrap()
+                    .insert(sent.JXBID.clone(), fresh);
+            }
+            None => {
+                let outcome = EnrollOutcome::RemovedFromFavorites;
+                {
+                    let mut outcomes = state.outcomes.lock().unwrap();
+                    // 其他工作线程可能已经选上，不能覆盖
+                    if outcomes
+                        .get(&sent.JXBID)
+                        .is_some_and(|o| self.is_terminal(o))
+                    {
+                        return;
+                    }
+                    outcomes.insert(sent.JXBID.clone(), outcome.clone());
+                }
+                self.emit(EnrollEvent::CourseFinished {
+                    class_id: sent.JXBID.clone(),
+                    name: sent.KCM.clone(),
+                    outcome,
+                });
+            }
+        }
+    }
+
+    /// Retrying after this outcome cannot change the result
+    fn is_terminal(&self, outcome: &EnrollOutcome) -> bool {
+        match outcome {
+            EnrollOutcome::Success | EnrollOutcome::AlreadySelected => true,
+            EnrollOutcome::AlternativeSelected { .. } => true,
+            EnrollOutcome::Unauthorized | EnrollOutcome::RemovedFromFavorites => true,
+            EnrollOutcome::CapacityFull => !self.config.try_if_capacity_full,
+            _ => false,
+        }
+    }
+
+    fn is_finished(&self, state: &RunState, class_id: &str) -> bool {
+        state
+            .outcomes
+            .lock()
+            .unwrap()
+            .get(class_id)
+            .is_some_and(|o| self.is_terminal(o))
+            || state.abandoned.lock().unwrap().contains(class_id)
+    }
+
+    fn is_running(&self) -> bool {
+        !self.cancel.is_cancelled()
+    }
+
+    pub(super) fn emit(&self, event: EnrollEvent) {
+        self.subscribers.emit(event);
+    }
+}
@@ -0,0 +1,37 @@
+//! Event fan-out shared by the engine, the account pool and the session
+//!
+//! Every event source keeps a [`Subscribers`] list; front ends call
+//! `subscribe` on the source and render what arrives.
+
+use std::sync::Mutex as StdMutex;
+use tokio::sync::mpsc;
+
+/// Channels of everyone subscribed to one event source
+pub(crate) struct Subscribers<T> {
+    senders: StdMutex<Vec<mpsc::UnboundedSender<T>>>,
+}
+
+impl<T> Default for Subscribers<T> {
+    fn default() -> Self {
+        Self {
+            senders: StdMutex::new(Vec::new()),
+        }
+    }
+}
+
+impl<T: Clone> Subscribers<T> {
+    /// Receive the events emitted from now on
+    pub(crate) fn subscribe(&self) -> mpsc::UnboundedReceiver<T> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        self.senders.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// Send `event` to every subscriber, forgetting those that hung up
+    pub(crate) fn emit(&self, event: T) {
+        self.senders
+            .lock()
+            .unwrap()
+            .retain(|sender| sender.send(event.clone()).is_ok());
+    }
+}
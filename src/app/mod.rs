@@ -9,6 +9,26 @@
 pub mod request;
 #[cfg(feature = "no-wasm")]
 pub use request::*;
+#[cfg(feature = "no-wasm")]
+pub mod captcha;
+#[cfg(feature = "no-wasm")]
+pub mod config;
+#[cfg(feature = "no-wasm")]
+pub mod engine;
+#[cfg(feature = "no-wasm")]
+mod events;
+#[cfg(feature = "no-wasm")]
+pub mod pool;
+#[cfg(feature = "no-wasm")]
+pub mod reconcile;
+#[cfg(feature = "no-wasm")]
+pub mod scheduler;
+#[cfg(feature = "no-wasm")]
+pub mod session;
+#[cfg(feature = "no-wasm")]
+pub mod store;
+#[cfg(feature = "no-wasm")]
+pub mod vault;
 
 #[cfg(feature = "wasm")]
 pub mod gloo;
@@ -0,0 +1,238 @@
+//! Enrollment for several accounts in one process
+//!
+//! [`AccountPool`] gives every account a [`NoWasmClient`] of its own, so
+//! cookies, tokens, batch selection and course lists never mix, while all
+//! clients share one [`RateLimiter`] and therefore one request budget. Each
+//! account runs its own [`EnrollmentEngine`]; their events are forwarded as
+//! [`PoolEvent`]s tagged with the account.
+
+use crate::client::config::ServerConfig;
+use crate::client::rate_limit::RateLimiter;
+use crate::client::request::NoWasmClient;
+use crate::error::Result;
+use crate::interface::HttpClient;
+use crate::model::structs::{BatchSelector, CourseTarget};
+use futures::future::join_all;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_util::sync::CancellationToken;
+
+use super::captcha::CaptchaSolver;
+use super::config::{TargetResolution, resolve_targets};
+use super::engine::{EnrollConfig, EnrollEvent, EnrollReport, EnrollmentEngine};
+use super::events::Subscribers;
+use super::reconcile::SameCourse;
+use super::session::{Credentials, Session, SessionManager};
+
+pub struct PoolAccount {
+    pub username: String,
+    pub session: Arc<SessionManager>,
+    /// Resolved like [`AppConfig::courses`](super::config::AppConfig::courses)
+    pub courses: Vec<CourseTarget>,
+    /// Resolved like [`AppConfig::groups`](super::config::AppConfig::groups)
+    pub groups: Vec<Vec<CourseTarget>>,
+}
+
+/// Progress reported by [`AccountPool::run`]
+#[derive(Debug, Clone)]
+pub enum PoolEvent {
+    /// Event of the engine running for `account`
+    Enroll { account: String, event: EnrollEvent },
+    /// `account` could not log in or fetch its courses and was skipped
+    Failed { account: String, error: String },
+}
+
+/// Outcome of one account in a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountReport {
+    pub account: String,
+    /// Courses that were still to be selected when the run started
+    pub report: Option<EnrollReport>,
+    /// Targets found neither in the favorites nor in the selected courses
+    #[serde(default)]
+    pub unresolved: Vec<CourseTarget>,
+    /// Favorites left out because another class of the same course is selected
+    #[serde(default)]
+    pub same_course_selected: Vec<SameCourse>,
+    pub error: Option<String>,
+}
+
+impl AccountReport {
+    /// Every course found for the account is selected, or none was left
+    ///
+    /// Targets that were not found may remain, see [`is_done`](Self::is_done).
+    pub fn is_finished(&self) -> bool {
+        self.report.as_ref().is_some_and(|report| {
+            report
+                .courses
+                .iter()
+                .all(|c| c.outcome.as_ref().is_some_and(|o| o.is_selected()))
+        })
+    }
+
+    /// Every target of the account was found and is selected
+    pub fn is_done(&self) -> bool {
+        self.unresolved.is_empty() && self.is_finished()
+    }
+}
+
+pub struct AccountPool {
+    limiter: Arc<RateLimiter>,
+    config: EnrollConfig,
+    accounts: Vec<PoolAccount>,
+    cancel: CancellationToken,
+    subscribers: Subscribers<PoolEvent>,
+}
+
+impl AccountPool {
+    /// Empty pool whose clients all go through `limiter`
+    pub fn new(limiter: Arc<RateLimiter>, config: EnrollConfig) -> Self {
+        Self {
+            limiter,
+            config,
+            accounts: Vec::new(),
+            cancel: CancellationToken::new(),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Stop every account once `cancel` is cancelled
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    pub fn limiter(&self) -> &Arc<RateLimiter> {
+        &self.limiter
+    }
+
+    pub fn accounts(&self) -> &[PoolAccount] {
+        &self.accounts
+    }
+
+    /// Add an account with a fresh client for `server`
+    pub async fn add_account(
+        &mut self,
+        server: ServerConfig,
+        credentials: Credentials,
+        batch: impl Into<BatchSelector>,
+        courses: Vec<CourseTarget>,
+        groups: Vec<Vec<CourseTarget>>,
+        solver: Arc<dyn CaptchaSolver>,
+    ) -> Result<()> {
+        let client = NoWasmClient::with_config(server)
+            .await?
+            .with_rate_limiter(Arc::clone(&self.limiter));
+        let username = credentials.username.clone();
+        let session = SessionManager::new(client, credentials, batch, solver);
+        self.push(PoolAccount {
+            username,
+            session: Arc::new(session),
+            courses,
+            groups,
+        });
+        Ok(())
+    }
+
+    /// Add an account whose session was built elsewhere
+    pub fn push(&mut self, account: PoolAccount) {
+        self.accounts.push(account);
+    }
+
+    /// Receive the events of subsequent runs
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<PoolEvent> {
+        self.subscribers.subscribe()
+    }
+
+    /// Log in every account one after another, so captcha prompts do not overlap
+    pub async fn login_all(&self) -> Vec<Result<Session>> {
+        let mut sessions = Vec::with_capacity(self.accounts.len());
+        for account in &self.accounts {
+            sessions.push(account.session.current().await);
+        }
+        sessions
+    }
+
+    /// Resolve the targets of every account one after another, without enrolling
+    pub async fn resolve_all(&self) -> Vec<Result<TargetResolution>> {
+        let mut resolutions = Vec::with_capacity(self.accounts.len());
+        for account in &self.accounts {
+            resolutions.push(self.resolve(account).await);
+        }
+        resolutions
+    }
+
+    /// Enroll every account in its remaining target courses concurrently
+    pub async fn run(&self) -> Vec<AccountReport> {
+        join_all(
+            self.accounts
+                .iter()
+                .map(|account| self.run_account(account)),
+        )
+        .await
+    }
+
+    async fn run_account(&self, account: &PoolAccount) -> AccountReport {
+        let resolution = match self.resolve(account).await {
+            Ok(resolution) => resolution,
+            Err(e) => {
+                let error = e.to_string();
+                self.subscribers.emit(PoolEvent::Failed {
+                    account: account.username.clone(),
+                    error: error.clone(),
+                });
+                return AccountReport {
+                    account: account.username.clone(),
+                    report: None,
+                    unresolved: Vec::new(),
+                    same_course_selected: Vec::new(),
+                    error: Some(error),
+                };
+            }
+        };
+
+        let config = EnrollConfig {
+            groups: resolution.groups,
+            ..self.config.clone()
+        };
+        let engine = EnrollmentEngine::new(Arc::clone(&account.session), config)
+            .with_cancellation(self.cancel.child_token());
+        let mut events = engine.subscribe();
+        let forward = async {
+            while let Some(event) = events.recv().await {
+                let finished = matches!(event, EnrollEvent::Finished(_));
+                self.subscribers.emit(PoolEvent::Enroll {
+                    account: account.username.clone(),
+                    event,
+                });
+                if finished {
+                    break;
+                }
+            }
+        };
+        let (result, ()) = futures::join!(engine.run(&resolution.courses), forward);
+
+        let (report, error) = match result {
+            Ok(report) => (Some(report), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        AccountReport {
+            account: account.username.clone(),
+            report,
+            unresolved: resolution.unresolved,
+            same_course_selected: resolution.same_course_selected,
+            error,
+        }
+    }
+
+    async fn resolve(&self, account: &PoolAccount) -> Result<TargetResolution> {
+        let (selected, favorites) = account.session.get_courses().await?;
+        Ok(resolve_targets(
+            &account.courses,
+            &account.groups,
+            &favorites,
+            &selected,
+        ))
+    }
+}
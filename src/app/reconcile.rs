@@ -0,0 +1,65 @@
+//! Reconciliation of the favorites with the selected courses
+//!
+//! Requesting a course that is selected already only earns
+//! `该课程已在选课结果中`, so [`reconcile`] drops such favorites before any
+//! worker starts: by JXBID when the very class is selected, and by KCM when
+//! another class of the same course is.
+
+use crate::model::structs::CourseInfo;
+use serde::{Deserialize, Serialize};
+
+/// Favorites split by what the selected list already covers
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Reconciliation {
+    /// Favorites that still have to be requested
+    pub to_enroll: Vec<CourseInfo>,
+    /// Favorites whose class is selected
+    pub already_selected: Vec<CourseInfo>,
+    /// Favorites with another class of the same course selected
+    pub same_course_selected: Vec<SameCourse>,
+}
+
+/// A favorite left out for a selected class of the same course
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SameCourse {
+    pub favorite: CourseInfo,
+    pub selected: CourseInfo,
+}
+
+impl Reconciliation {
+    /// The favorite with `class_id` was left out
+    pub fn is_skipped(&self, class_id: &str) -> bool {
+        self.already_selected.iter().any(|c| c.JXBID == class_id)
+            || self
+                .same_course_selected
+                .iter()
+                .any(|s| s.favorite.JXBID == class_id)
+    }
+}
+
+/// Diff `favorites` against `selected`, keeping the order of the favorites
+pub fn reconcile(favorites: &[CourseInfo], selected: &[CourseInfo]) -> Reconciliation {
+    let mut reconciliation = Reconciliation::default();
+    for course in favorites {
+        // 收藏列表中可能有重复的教学班
+        if reconciliation
+            .to_enroll
+            .iter()
+            .any(|c| c.JXBID == course.JXBID)
+            || reconciliation.is_skipped(&course.JXBID)
+        {
+            continue;
+        }
+        if selected.iter().any(|s| s.JXBID == course.JXBID) {
+            reconciliation.already_selected.push(course.clone());
+        } else if let Some(same) = selected.iter().find(|s| s.KCM == course.KCM) {
+            reconciliation.same_course_selected.push(SameCourse {
+                favorite: course.clone(),
+                selected: same.clone(),
+            });
+        } else {
+            reconciliation.to_enroll.push(course.clone());
+        }
+    }
+    reconciliation
+}
@@ -4,202 +4,216 @@
 //! including both TUI and GUI implementations.
 
 #[cfg(all(feature = "no-wasm", feature = "gui"))]
-use crate::model::structs::EnrollmentStatus;
+use crate::model::structs::{CompletionReason, CourseOutcome, EnrollmentStatus};
 use crate::{
-    client::request,
+    client::request::NoWasmClient,
     crypto,
     error::{ErrorKind, Result},
+    interface::RequestApi,
+    model::dtos::{CourseQueryParams, LoginParams},
 };
-use futures::future::join_all;
-use reqwest::Client;
-use std::{collections::HashMap, sync::Arc, time::Duration};
 
+use super::captcha::CaptchaSolver;
+#[cfg(all(feature = "no-wasm", any(feature = "tui", feature = "gui")))]
+use super::engine::{EnrollConfig, EnrollEvent, EnrollmentEngine};
+#[cfg(all(feature = "no-wasm", any(feature = "tui", feature = "gui")))]
+use super::session::SessionManager;
+#[cfg(all(feature = "no-wasm", any(feature = "tui", feature = "gui")))]
+use std::sync::Arc;
+#[cfg(all(feature = "no-wasm", any(feature = "tui", feature = "gui")))]
+use tokio::sync::mpsc::UnboundedReceiver;
+
+#[cfg(all(feature = "no-wasm", feature = "tui"))]
+use super::engine::EnrollReport;
+#[cfg(all(feature = "no-wasm", feature = "gui"))]
+use super::engine::Strategy;
 #[cfg(all(feature = "no-wasm", feature = "tui"))]
-use std::sync::Mutex as StdMutex;
+use super::scheduler::Scheduler;
+use super::scheduler::{BatchWindow, shanghai};
+use chrono::{DateTime, FixedOffset, Utc};
+#[cfg(all(feature = "no-wasm", feature = "gui"))]
+use std::time::Duration;
 #[cfg(all(feature = "no-wasm", feature = "gui"))]
 use tokio::sync::Mutex as TokioMutex;
 
-use crate::model::structs::{BatchInfo, CourseInfo};
-
-const WORK_THREAD_COUNT: usize = 4;
+use crate::model::responses::CaptchaData;
+#[cfg(all(feature = "no-wasm", feature = "tui"))]
+use crate::model::responses::LoginData;
+#[cfg(all(feature = "no-wasm", any(feature = "tui", feature = "gui")))]
+use crate::model::structs::EnrollOutcome;
+use crate::model::structs::{BatchInfo, BatchSelector, CourseInfo};
 
 // GUI-specific functionality
 #[cfg(all(feature = "no-wasm", feature = "gui"))]
 pub mod gui {
     use super::*;
+    use tokio_util::sync::CancellationToken;
 
-    pub async fn login(
-        client: &Client,
-        username: &str,
-        password: &str,
-        captcha: &str, // GUI模式下直接接收验证码
-        uuid: &str,    // GUI模式下直接接收uuid
-    ) -> Result<(String, Vec<BatchInfo>)> {
-        // Get AES key
-        let aes_key = request::get_aes_key(client).await?;
-
-        // Encrypt password and login
-        let encrypted_password = crypto::encrypt_password(password, &aes_key)?;
-        let login_resp =
-            request::send_login_request(client, username, &encrypted_password, captcha, uuid)
-                .await?;
-
-        if login_resp["code"] == 200 && login_resp["msg"] == "登录成功" {
-            let token = login_resp["data"]["token"]
-                .as_str()
-                .ok_or_else(|| ErrorKind::ParseError("Invalid token".to_string()))?
-                .to_string();
-
-            let batch_list =
-                serde_json::from_value(login_resp["data"]["student"]["electiveBatchList"].clone())?;
-
-            Ok((token, batch_list))
-        } else {
-            Err(ErrorKind::ParseError(login_resp["msg"].to_string()).into())
-        }
-    }
-
-    pub async fn get_captcha_inner(client: &Client) -> Result<(String, String)> {
-        let (uuid, captcha_b64) = request::get_captcha(client).await?;
-        let captcha_img = crypto::decode_captcha_image(&captcha_b64)?;
+    /// Fetch a captcha as `(uuid, base64 png)` for display
+    pub async fn get_captcha_inner(client: &NoWasmClient) -> Result<(String, String)> {
+        let CaptchaData { uuid, captcha } = client.get_captcha().await?;
+        let captcha_img = crypto::decode_captcha_image(&captcha)?;
         let base64 = base64_simd::STANDARD;
-        std::fs::write("captcha.png", &captcha_img)?;
         Ok((uuid, base64.encode_to_string(captcha_img)))
     }
 
     pub async fn enroll_courses(
-        client: &Client,
+        client: &NoWasmClient,
         token: &str,
         batch_id: &str,
         courses: &[CourseInfo],
         try_if_capacity_full: bool,
         status: Arc<TokioMutex<EnrollmentStatus>>,
-        should_continue: Arc<TokioMutex<bool>>,
+        cancel: CancellationToken,
     ) -> Result<()> {
-        if courses.is_empty() {
-            return Ok(());
+        let session = Arc::new(SessionManager::with_token(client.clone(), token, batch_id));
+        let config = EnrollConfig {
+            strategy: Strategy::Continuous,
+            try_if_capacity_full,
+            interval: Duration::from_millis(500),
+            ..EnrollConfig::default()
+        };
+        enroll_courses_with_session(&session, courses, &config, status, cancel).await
+    }
+
+    /// Enroll using a session that re-logs in when the token expires
+    ///
+    /// Runs until every course has a final outcome or `cancel` is cancelled,
+    /// then leaves `status` stopped with the reason.
+    pub async fn enroll_courses_with_session(
+        session: &Arc<SessionManager>,
+        courses: &[CourseInfo],
+        config: &EnrollConfig,
+        status: Arc<TokioMutex<EnrollmentStatus>>,
+        cancel: CancellationToken,
+    ) -> Result<()> {
+        let engine =
+            EnrollmentEngine::new(Arc::clone(session), config.clone()).with_cancellation(cancel);
+        let events = engine.subscribe();
+        {
+            let course_outcomes = pending_outcomes(courses);
+            let mut status = status.lock().await;
+            status.is_running = true;
+            status.completion = None;
+            // 清除上一次运行的计数和课程状态
+            status.total_requests = 0;
+            status.course_statuses = course_statuses(&course_outcomes);
+            status.course_outcomes = course_outcomes;
         }
 
-        let current_status: Arc<TokioMutex<HashMap<String, String>>> =
-            Arc::new(TokioMutex::new(HashMap::new()));
-        let mut tasks = Vec::new();
-        let total_requests = Arc::new(TokioMutex::new(0u32));
-
-        // 为每个课程创建一个任务
-        for thread_id in 0..WORK_THREAD_COUNT {
-            let client = client.clone();
-            let token = token.to_string();
-            let batch_id = batch_id.to_string();
-            let courses = courses.to_vec();
-            let status_map = Arc::clone(&current_status);
-            let enrollment_status = Arc::clone(&status);
-            let should_continue = Arc::clone(&should_continue);
-            let total_requests = Arc::clone(&total_requests);
-            let course_count = courses.len();
-
-            tasks.push(tokio::spawn(async move {
-                let mut course_idx = thread_id % course_count;
-
-                while *should_continue.lock().await {
-                    let course = &courses[course_idx];
-
-                    // 更新状态
+        let (result, ()) = tokio::join!(
+            engine.run(courses),
+            update_status(events, courses, Arc::clone(&status))
+        );
+
+        let mut status = status.lock().await;
+        status.is_running = false;
+        status.completion = Some(match &result {
+            Ok(report) if report.is_complete() => CompletionReason::AllFinished,
+            Ok(_) => CompletionReason::Stopped,
+            Err(e) => CompletionReason::Failed {
+                message: e.to_string(),
+            },
+        });
+        result.map(|_| ())
+    }
+
+    /// Mirror engine events into the shared `EnrollmentStatus`
+    async fn update_status(
+        mut events: UnboundedReceiver<EnrollEvent>,
+        courses: &[CourseInfo],
+        status: Arc<TokioMutex<EnrollmentStatus>>,
+    ) {
+        let mut course_outcomes = pending_outcomes(courses);
+        let mut total_requests = 0;
+        while let Some(event) = events.recv().await {
+            let finished = match event {
+                EnrollEvent::Attempt {
+                    class_id,
+                    outcome,
+                    total_requests: total,
+                    ..
+                } => {
+                    total_requests = total;
+                    if let Some(course) = course_outcomes
+                        .iter_mut()
+                        .find(|c| c.class_id == class_id && !c.finished)
                     {
-                        let mut counter = total_requests.lock().await;
-                        *counter += 1;
-                        let statuses: Vec<String> = {
-                            let status_map = status_map.lock().await;
-                            courses
-                                .iter()
-                                .map(|c| {
-                                    format!(
-                                        "[{}]{}",
-                                        c.KCM,
-                                        status_map.get(&c.JXBID).unwrap_or(&"等待中".to_string())
-                                    )
-                                })
-                                .collect()
-                        };
-
-                        let mut status = enrollment_status.lock().await;
-                        status.total_requests = *counter;
-                        status.course_statuses = statuses;
+                        course.outcome = Some(outcome);
                     }
-
-                    // 尝试选课
-                    let _result = course_enrollment_worker(
-                        client.clone(),
-                        token.clone(),
-                        batch_id.clone(),
-                        course.clone(),
-                        Arc::clone(&status_map),
-                        try_if_capacity_full,
-                    )
-                    .await;
-
-                    if !*should_continue.lock().await {
-                        break;
+                    false
+                }
+                // 同组课程选上后其余课程只有结束事件
+                EnrollEvent::CourseFinished {
+                    class_id, outcome, ..
+                } => {
+                    if let Some(course) =
+                        course_outcomes.iter_mut().find(|c| c.class_id == class_id)
+                    {
+                        course.outcome = Some(outcome);
+                        course.finished = true;
                     }
-
-                    course_idx = (course_idx + 1) % course_count;
-
-                    // 短暂延迟避免请求过快
-                    tokio::time::sleep(Duration::from_millis(500)).await;
+                    false
+                }
+                EnrollEvent::Verified {
+                    class_id,
+                    confirmed,
+                    ..
+                } => {
+                    if let Some(course) =
+                        course_outcomes.iter_mut().find(|c| c.class_id == class_id)
+                    {
+                        course.confirmed = Some(confirmed);
+                    }
+                    false
                 }
-            }));
+                EnrollEvent::Finished(report) => {
+                    total_requests = report.total_requests;
+                    course_outcomes = report.courses;
+                    true
+                }
+                EnrollEvent::WarmingUp | EnrollEvent::Starting { .. } | EnrollEvent::BatchEnded => {
+                    continue;
+                }
+            };
+
+            let mut status = status.lock().await;
+            status.total_requests = total_requests;
+            status.course_statuses = course_statuses(&course_outcomes);
+            status.course_outcomes = course_outcomes.clone();
+            if finished {
+                break;
+            }
         }
+    }
 
-        join_all(tasks).await;
-        Ok(())
+    /// Every course of a run before its first request
+    fn pending_outcomes(courses: &[CourseInfo]) -> Vec<CourseOutcome> {
+        courses
+            .iter()
+            .map(|c| CourseOutcome {
+                class_id: c.JXBID.clone(),
+                name: c.KCM.clone(),
+                outcome: None,
+                confirmed: None,
+                finished: false,
+            })
+            .collect()
     }
 
-    async fn course_enrollment_worker(
-        client: Client,
-        token: String,
-        batch_id: String,
-        course: CourseInfo,
-        status_map: Arc<TokioMutex<HashMap<String, String>>>,
-        try_if_capacity_full: bool,
-    ) -> Result<()> {
-        let result = request::select_course(
-            &client,
-            &token,
-            &batch_id,
-            &course.teaching_class_type.clone().unwrap_or_default(),
-            &course.JXBID,
-            &course.secret_val.clone().unwrap_or_default(),
-        )
-        .await;
-
-        match result {
-            Ok(json) => {
-                let code = json["code"].as_i64().unwrap_or(0);
-                let msg = json["msg"].as_str().unwrap_or("");
-
-                let status = match (code, msg) {
-                    (200, _) => "选课成功",
-                    (500, "该课程已在选课结果中") => "已选",
-                    (500, "本轮次选课暂未开始") => "未开始",
-                    (500, "课容量已满") if !try_if_capacity_full => "已满",
-                    (500, "课容量已满") => "等待中",
-                    (500, "参数校验不通过") => "参数错误",
-                    (401, _) => "未登录",
-                    _ => "失败",
-                };
-
-                status_map
-                    .lock()
-                    .await
-                    .insert(course.JXBID.clone(), status.to_string());
-                Ok(())
-            }
-            Err(e) => {
-                status_map
-                    .lock()
-                    .await
-                    .insert(course.JXBID.clone(), "请求错误".to_string());
-                Err(e)
-            }
+    fn course_statuses(courses: &[CourseOutcome]) -> Vec<String> {
+        courses
+            .iter()
+            .map(|c| format!("[{}]{}", c.name, status_label(c)))
+            .collect()
+    }
+
+    /// GUI label for a course, `等待中` while it is still being retried
+    fn status_label(course: &CourseOutcome) -> &'static str {
+        match &course.outcome {
+            None => "等待中",
+            Some(EnrollOutcome::CapacityFull) if !course.finished => "等待中",
+            Some(outcome) => outcome.label(),
         }
     }
 }
@@ -208,231 +222,177 @@ pub mod gui {
 #[cfg(all(feature = "no-wasm", feature = "tui"))]
 pub mod tui {
     use super::*;
-
-    pub async fn login(
-        client: &Client,
-        username: &str,
-        password: &str,
-    ) -> Result<(String, Vec<BatchInfo>)> {
-        // Get AES key
-        let aes_key = request::get_aes_key(client).await?;
-
-        // Get and save captcha
-        let (uuid, captcha_b64) = request::get_captcha(client).await?;
-        let captcha_img: Vec<u8> = crypto::decode_captcha_image(&captcha_b64)?;
-        std::fs::write("captcha.png", captcha_img)?;
-
-        // Get captcha input
-        println!("Please check captcha.png and enter the captcha:");
-        std::io::Write::flush(&mut std::io::stdout())?;
-        let mut captcha = String::new();
-        std::io::stdin().read_line(&mut captcha)?;
-        let captcha = captcha.trim().to_string();
-
-        // Encrypt password and login
-        let encrypted_password = crypto::encrypt_password(password, &aes_key)?;
-        let login_resp =
-            request::send_login_request(client, username, &encrypted_password, &captcha, &uuid)
-                .await?;
-
-        if login_resp["code"] == 200 && login_resp["msg"] == "登录成功" {
-            let token = login_resp["data"]["token"]
-                .as_str()
-                .ok_or_else(|| ErrorKind::ParseError("Invalid token".to_string()))?
-                .to_string();
-
-            let batch_list =
-                serde_json::from_value(login_resp["data"]["student"]["electiveBatchList"].clone())?;
-
-            print_login_success(&login_resp);
-            Ok((token, batch_list))
-        } else {
-            println!("Login failed: {}", login_resp["msg"]);
-            Err(ErrorKind::ParseError("Login failed".to_string()).into())
-        }
-    }
+    use crate::app::config::TargetResolution;
+    use crate::app::session::SessionEvent;
+    use crate::model::structs::CourseOutcome;
+    use tokio_util::sync::CancellationToken;
 
     pub async fn enroll_courses(
-        client: &Client,
+        client: &NoWasmClient,
         token: &str,
         batch_id: &str,
         courses: &[CourseInfo],
         try_if_capacity_full: bool,
     ) -> Result<()> {
+        let session = Arc::new(SessionManager::with_token(client.clone(), token, batch_id));
+        let config = EnrollConfig {
+            try_if_capacity_full,
+            ..EnrollConfig::default()
+        };
+        enroll_courses_with_session(&session, courses, &config, &CancellationToken::new())
+            .await
+            .map(|_| ())
+    }
+
+    /// Enroll using a session that re-logs in when the token expires
+    pub async fn enroll_courses_with_session(
+        session: &Arc<SessionManager>,
+        courses: &[CourseInfo],
+        config: &EnrollConfig,
+        cancel: &CancellationToken,
+    ) -> Result<EnrollReport> {
         if courses.is_empty() {
-            return Ok(());
+            return Ok(EnrollReport::default());
         }
 
-        let current_status: Arc<StdMutex<HashMap<String, String>>> =
-            Arc::new(StdMutex::new(HashMap::new()));
-        let mut tasks = Vec::new();
-
-        // 创建 WORK_THREAD_COUNT 个工作线程
-        for thread_id in 0..WORK_THREAD_COUNT {
-            let client = client.clone();
-            let token = token.to_string();
-            let batch_id = batch_id.to_string();
-            let courses = courses.to_vec(); // 克隆整个课程列表
-            let status = Arc::clone(&current_status);
-            let course_count = courses.len();
-
-            tasks.push(tokio::spawn(async move {
-                // 从不同位置开始遍历课程
-                let mut course_idx = thread_id % course_count;
-                for _ in 0..course_count {
-                    let course = &courses[course_idx];
-                    let class_type = course.teaching_class_type.clone().unwrap_or_default();
-                    let class_id = course.JXBID.clone();
-                    let secret_val = course.secret_val.clone().unwrap_or_default();
-                    let name = course.KCM.clone();
+        let engine = EnrollmentEngine::new(Arc::clone(session), config.clone())
+            .with_cancellation(cancel.child_token());
+        let events = engine.subscribe();
 
-                    {
-                        let mut status = status.lock().unwrap();
-                        if !status.contains_key(&class_id) {
-                            status.insert(class_id.clone(), "doing".to_string());
-                        }
-                    }
-
-                    // 尝试选课
-                    course_enrollment_worker(
-                        client.clone(),
-                        token.clone(),
-                        batch_id.clone(),
-                        class_type,
-                        class_id,
-                        secret_val,
-                        name,
-                        Arc::clone(&status),
-                        try_if_capacity_full,
-                    )
-                    .await;
-
-                    // 循环移动到下一个课程
-                    course_idx = (course_idx + 1) % course_count;
-                }
-            }));
+        let (result, ()) = tokio::join!(engine.run(courses), print_events(events));
+        let report = result?;
+        if !cancel.is_cancelled() {
+            println!("本轮抢课结束，继续检查...");
         }
+        Ok(report)
+    }
 
-        join_all(tasks).await;
-        println!("本轮抢课结束，继续检查...");
-        Ok(())
+    /// Wait for the batch to open, then enroll until it closes or `cancel`
+    /// is cancelled
+    pub async fn enroll_courses_scheduled(
+        scheduler: &Scheduler,
+        courses: &[CourseInfo],
+        config: &EnrollConfig,
+        cancel: &CancellationToken,
+    ) -> Result<EnrollReport> {
+        // 批次结束时调度器只取消本轮
+        let engine = EnrollmentEngine::new(Arc::clone(scheduler.session()), config.clone())
+            .with_cancellation(cancel.child_token());
+        let printer = tokio::spawn(print_events(engine.subscribe()));
+
+        let result = scheduler.run(&engine, courses).await;
+        // 关闭事件通道，等待打印结束
+        drop(engine);
+        let _ = printer.await;
+        result
     }
 
-    #[allow(clippy::too_many_arguments)]
-    async fn course_enrollment_worker(
-        client: Client,
-        token: String,
-        batch_id: String,
-        class_type: String,
-        class_id: String,
-        secret_val: String,
-        name: String,
-        current_status: Arc<StdMutex<HashMap<String, String>>>,
-        try_if_capacity_full: bool,
-    ) {
-        loop {
-            // 检查课程状态
-            {
-                let status = current_status.lock().unwrap();
-                if status.get(&class_id) != Some(&"doing".to_string()) {
-                    break;
+    /// Print engine events as they arrive
+    async fn print_events(mut events: UnboundedReceiver<EnrollEvent>) {
+        while let Some(event) = events.recv().await {
+            let (name, outcome) = match event {
+                EnrollEvent::Attempt { name, outcome, .. } => (name, outcome),
+                // 以下情况不会再请求，只有结束事件
+                EnrollEvent::CourseFinished {
+                    name,
+                    outcome:
+                        outcome @ (EnrollOutcome::AlternativeSelected { .. }
+                        | EnrollOutcome::RemovedFromFavorites),
+                    ..
+                } => (name, outcome),
+                EnrollEvent::CourseFinished {
+                    name,
+                    outcome: EnrollOutcome::ParamInvalid,
+                    ..
+                } => {
+                    println!("[{name}] 刷新收藏后仍然参数校验不通过，放弃");
+                    continue;
                 }
-            }
-
-            let result = request::select_course(
-                &client,
-                &token,
-                &batch_id,
-                &class_type,
-                &class_id,
-                &secret_val,
-            )
-            .await;
-
-            match result {
-                Ok(json) => {
-                    let code = json["code"].as_i64().unwrap_or(0);
-                    let msg = json["msg"].as_str().unwrap_or("");
-
-                    let mut status = current_status.lock().unwrap();
-                    if status.get(&class_id) == Some(&"doing".to_string()) {
-                        match (code, msg) {
-                            (200, _) => {
-                                println!("选课成功 [{name}]");
-                                status.insert(class_id.clone(), "done".to_string());
-                                break;
-                            }
-                            (500, "该课程已在选课结果中") => {
-                                println!("[{name}] {msg}");
-                                status.insert(class_id.clone(), "done".to_string());
-                                break;
-                            }
-                            (500, "本轮次选课暂未开始") => {
-                                println!("[{name}]本轮次选课暂未开始");
-                                continue;
-                            }
-                            (500, "课容量已满") => {
-                                println!("{name}课容量已满");
-                                if !try_if_capacity_full {
-                                    break;
-                                }
-                                continue;
-                            }
-                            (500, "参数校验不通过") => {
-                                println!("[{json:?}]");
-                                continue;
-                            }
-                            (401, _) => {
-                                println!("{msg}");
-                                break;
-                            }
-                            _ => {
-                                println!("[{code}]: 失败，重试中...");
-                                continue;
-                            }
-                        }
+                EnrollEvent::Verified {
+                    name, confirmed, ..
+                } => {
+                    if confirmed {
+                        println!("[{name}] 已在选课结果中确认");
                     } else {
-                        break;
+                        println!("[{name}] 返回选课成功，但选课结果中未找到该课程");
                     }
+                    continue;
+                }
+                EnrollEvent::CourseFinished { .. } => continue,
+                // 调度进度输出到 stderr
+                EnrollEvent::WarmingUp => {
+                    eprintln!("选课即将开始，刷新登录状态...");
+                    continue;
                 }
-                Err(e) => {
-                    println!("请求错误: {e}，重试中...");
-                    tokio::time::sleep(Duration::from_millis(500)).await;
+                EnrollEvent::Starting { server_time } => {
+                    eprintln!("开始选课 (服务器时间 {server_time})");
                     continue;
                 }
+                EnrollEvent::BatchEnded => {
+                    eprintln!("选课批次已结束，停止选课");
+                    continue;
+                }
+                EnrollEvent::Finished(_) => break,
+            };
+
+            match outcome {
+                EnrollOutcome::Success => println!("选课成功 [{name}]"),
+                EnrollOutcome::AlreadySelected => println!("[{name}] 该课程已在选课结果中"),
+                EnrollOutcome::NotStarted => println!("[{name}]本轮次选课暂未开始"),
+                EnrollOutcome::CapacityFull => println!("{name}课容量已满"),
+                EnrollOutcome::ParamInvalid => println!("[{name}] 参数校验不通过，刷新收藏后重试"),
+                EnrollOutcome::Unauthorized => println!("[{name}] 登录已失效"),
+                EnrollOutcome::Unknown { code, msg } => println!("[{code}]: {msg} 失败，重试中..."),
+                EnrollOutcome::Transport { message } => println!("请求错误: {message}，重试中..."),
+                EnrollOutcome::AlternativeSelected { .. } => {
+                    println!("[{name}] 已选同组课程，跳过")
+                }
+                EnrollOutcome::RemovedFromFavorites => println!("[{name}] 已不在收藏列表中，跳过"),
             }
         }
     }
 
-    fn print_login_success(login_resp: &serde_json::Value) {
-        if let Some(student) = login_resp["data"]["student"].as_object() {
-            println!("Login success!");
-            println!("=====================================");
-            println!("XH: {}", student["XH"].as_str().unwrap_or(""));
-            println!("XM: {}", student["XM"].as_str().unwrap_or(""));
-            println!("ZYMC: {}", student["ZYMC"].as_str().unwrap_or(""));
-            println!("=====================================");
-
-            if let Some(batch_list) = student["electiveBatchList"].as_array() {
-                for batch in batch_list {
-                    if let Some(batch) = batch.as_object() {
-                        println!("name: {}", batch["name"].as_str().unwrap_or(""));
-                        println!("BeginTime: {}", batch["beginTime"].as_str().unwrap_or(""));
-                        println!("EndTime: {}", batch["endTime"].as_str().unwrap_or(""));
-                        println!("=====================================");
-                    }
+    /// Print session events to stderr, prefixed with `account` if given
+    pub async fn print_session_events(
+        mut events: UnboundedReceiver<SessionEvent>,
+        account: Option<String>,
+    ) {
+        let prefix = account.map(|a| format!("[{a}] ")).unwrap_or_default();
+        while let Some(event) = events.recv().await {
+            match event {
+                SessionEvent::Relogin => eprintln!("{prefix}登录已过期，重新登录..."),
+                SessionEvent::CaptchaRejected { .. } => {
+                    eprintln!("{prefix}验证码错误，请重新输入")
                 }
             }
         }
     }
 
+    // 登录过程的提示输出到 stderr，stdout 只留给命令结果
+    pub(super) fn print_login_success(login_data: &LoginData) {
+        let student = &login_data.student;
+        eprintln!("Login success!");
+        eprintln!("=====================================");
+        eprintln!("XH: {}", student.XH);
+        eprintln!("XM: {}", student.XM);
+        eprintln!("ZYMC: {}", student.ZYMC);
+        eprintln!("=====================================");
+
+        for batch in &student.elective_batch_list {
+            eprintln!("name: {}", batch.name);
+            eprintln!("BeginTime: {}", batch.begin_time);
+            eprintln!("EndTime: {}", batch.end_time);
+            eprintln!("=====================================");
+        }
+    }
+
     pub fn print_batch_info(batch: &BatchInfo) {
-        println!("Selected BatchId:");
-        println!("=====================================");
-        println!("name: {}", batch.name);
-        println!("BeginTime: {}", batch.begin_time);
-        println!("EndTime: {}", batch.end_time);
-        println!("=====================================");
+        eprintln!("Selected BatchId:");
+        eprintln!("=====================================");
+        eprintln!("name: {}", batch.name);
+        eprintln!("BeginTime: {}", batch.begin_time);
+        eprintln!("EndTime: {}", batch.end_time);
+        eprintln!("=====================================");
     }
 
     pub fn print_courses(selected: &[CourseInfo], favorite: &[CourseInfo]) {
@@ -455,24 +415,187 @@ pub mod tui {
         }
         println!("============================================");
     }
+
+    /// Print what a round is about to request and what it leaves out
+    pub fn print_preflight(targets: &TargetResolution) {
+        println!("==================本轮选课==================");
+        for course in &targets.courses {
+            println!("将尝试: [{}] {} {}", course.KCM, course.SKJS, course.JXBID);
+        }
+        for course in &targets.already_selected {
+            println!(
+                "已选上，跳过: [{}] {} {}",
+                course.KCM, course.SKJS, course.JXBID
+            );
+        }
+        for same in &targets.same_course_selected {
+            println!(
+                "已选同一课程的其他教学班，跳过: [{}] {} {} (已选 {})",
+                same.favorite.KCM, same.favorite.SKJS, same.favorite.JXBID, same.selected.JXBID
+            );
+        }
+        for group in &targets.groups {
+            println!("同组备选: {}", group.class_ids.join(" > "));
+        }
+        if targets.courses.is_empty() {
+            println!("没有需要选的课程");
+        }
+        println!("============================================");
+    }
+
+    /// Print the final state of every course
+    pub fn print_summary(courses: &[CourseOutcome]) {
+        println!("==================选课结果==================");
+        for course in courses {
+            let state = match &course.outcome {
+                None => "未请求".to_string(),
+                Some(outcome) if course.finished => outcome.label().to_string(),
+                Some(outcome) => format!("{}，未完成", outcome.label()),
+            };
+            let confirmed = match course.confirmed {
+                Some(true) => " (已确认)",
+                Some(false) => " (未确认)",
+                None => "",
+            };
+            println!("[{}] {} {state}{confirmed}", course.name, course.class_id);
+        }
+        println!("============================================");
+    }
 }
 
 // Common functionality for both TUI and GUI
+
+/// Fetch a captcha, ask `solver` for the answer and log in
+pub async fn login(
+    client: &NoWasmClient,
+    username: &str,
+    password: &str,
+    solver: &dyn CaptchaSolver,
+) -> Result<(String, Vec<BatchInfo>)> {
+    let CaptchaData { uuid, captcha } = client.get_captcha().await?;
+    let captcha_img = crypto::decode_captcha_image(&captcha)?;
+    let captcha = solver.solve(captcha_img, uuid.clone()).await?;
+
+    login_with_captcha(client, username, password, &captcha, &uuid).await
+}
+
+/// Log in with a captcha answer already obtained for `uuid`
+pub async fn login_with_captcha(
+    client: &NoWasmClient,
+    username: &str,
+    password: &str,
+    captcha: &str,
+    uuid: &str,
+) -> Result<(String, Vec<BatchInfo>)> {
+    // Get AES key
+    let aes_key = client.get_aes_key().await?;
+
+    // Encrypt password and login
+    let encrypted_password = crypto::encrypt_password(password, &aes_key)?;
+    let login_resp = client
+        .send_login_request(LoginParams {
+            username: username.to_string(),
+            encrypted_password,
+            captcha: captcha.to_string(),
+            uuid: uuid.to_string(),
+        })
+        .await?;
+
+    if login_resp.code == 200 && login_resp.msg == "登录成功" {
+        let login_data = login_resp
+            .data
+            .ok_or_else(|| ErrorKind::ParseError("Missing login data".to_string()))?;
+
+        #[cfg(all(feature = "no-wasm", feature = "tui"))]
+        tui::print_login_success(&login_data);
+
+        Ok((login_data.token, login_data.student.elective_batch_list))
+    } else if login_resp.msg.contains("验证码") {
+        Err(ErrorKind::CaptchaWrong.into())
+    } else {
+        Err(ErrorKind::LoginRejected {
+            msg: login_resp.msg,
+        }
+        .into())
+    }
+}
+
+/// Position in `batch_list` of the batch chosen by `selector` at time `now`
+pub fn find_batch(
+    batch_list: &[BatchInfo],
+    selector: &BatchSelector,
+    now: DateTime<FixedOffset>,
+) -> Result<usize> {
+    let not_found = || ErrorKind::BatchNotFound {
+        selector: selector.clone(),
+        available: batch_list.to_vec(),
+    };
+
+    match selector {
+        BatchSelector::Index(idx) if *idx < batch_list.len() => Ok(*idx),
+        BatchSelector::Index(_) => Err(not_found().into()),
+        BatchSelector::Code(code) => batch_list
+            .iter()
+            .position(|b| b.code == *code)
+            .ok_or_else(|| not_found().into()),
+        BatchSelector::Name(name) => {
+            // 名称完全相同时优先，否则要求只有一个批次包含该名称
+            if let Some(idx) = batch_list.iter().position(|b| b.name == *name) {
+                return Ok(idx);
+            }
+            let matches: Vec<usize> = (0..batch_list.len())
+                .filter(|&i| batch_list[i].name.contains(name.as_str()))
+                .collect();
+            match matches[..] {
+                [idx] => Ok(idx),
+                [] => Err(not_found().into()),
+                _ => Err(ErrorKind::BatchAmbiguous {
+                    selector: selector.clone(),
+                    matches: matches.iter().map(|&i| batch_list[i].clone()).collect(),
+                }
+                .into()),
+            }
+        }
+        BatchSelector::Auto => {
+            let windows: Vec<(usize, BatchWindow)> = batch_list
+                .iter()
+                .enumerate()
+                .filter_map(|(i, b)| BatchWindow::from_batch(b).ok().map(|w| (i, w)))
+                .collect();
+            // 优先选择正在进行的批次，否则选择最近将要开始的批次
+            windows
+                .iter()
+                .filter(|(_, w)| w.is_open(now))
+                .min_by_key(|(_, w)| w.end)
+                .or_else(|| {
+                    windows
+                        .iter()
+                        .filter(|(_, w)| w.begin > now)
+                        .min_by_key(|(_, w)| w.begin)
+                })
+                .map(|(i, _)| *i)
+                .ok_or_else(|| not_found().into())
+        }
+    }
+}
+
 pub async fn set_batch(
-    client: &Client,
+    client: &NoWasmClient,
     token: &str,
     batch_list: &[BatchInfo],
-    batch_idx: usize,
+    batch: impl Into<BatchSelector>,
 ) -> Result<String> {
-    if batch_idx >= batch_list.len() {
-        return Err(ErrorKind::ParseError("Invalid batch index".to_string()).into());
-    }
+    let now = Utc::now().with_timezone(&shanghai());
+    let batch_idx = find_batch(batch_list, &batch.into(), now)?;
 
     let batch_id = batch_list[batch_idx].code.clone();
-    let resp = request::set_batch(client, &batch_id, token).await?;
+    let resp = client.set_batch(&batch_id, token).await?;
 
-    if resp["code"] != 200 {
-        return Err(ErrorKind::ParseError("Failed to set batch".to_string()).into());
+    if resp.code == 401 {
+        return Err(ErrorKind::TokenExpired.into());
+    }
+    if !resp.is_success() {
+        return Err(ErrorKind::CourseError(format!("Failed to set batch: {}", resp.msg)).into());
     }
 
     #[cfg(all(feature = "no-wasm", feature = "tui"))]
@@ -482,31 +605,26 @@ pub async fn set_batch(
 }
 
 pub async fn get_courses(
-    client: &Client,
+    client: &NoWasmClient,
     token: &str,
     batch_id: &str,
 ) -> Result<(Vec<CourseInfo>, Vec<CourseInfo>)> {
-    let selected = request::get_selected_courses(client, token, batch_id).await?;
-    let favorite = request::get_favorite_courses(client, token, batch_id).await?;
-
-    let selected_courses: Vec<CourseInfo> = if selected["code"] == 200 {
-        serde_json::from_value(selected["data"].clone())?
-    } else {
-        return Err(ErrorKind::CourseError(selected["msg"].to_string()).into());
-    };
-
-    let favorite_courses: Vec<CourseInfo> = if favorite["code"] == 200 {
-        serde_json::from_value(favorite["data"].clone())?
-    } else {
-        return Err(ErrorKind::CourseError(favorite["msg"].to_string()).into());
+    let params = CourseQueryParams {
+        token: token.to_string(),
+        batch_id: batch_id.to_string(),
     };
+    let selected = client.get_selected_courses(params.clone()).await?;
+    let favorite = client.get_favorite_courses(params).await?;
 
-    Ok((selected_courses, favorite_courses))
+    Ok((selected.into_data()?, favorite.into_data()?))
 }
 
-// Re-export specific functionality based on enabled features
+// Re-export specific functionality based on enabled features. With both
+// front ends enabled the TUI enrollment adapters win; use `gui::` explicitly.
 #[cfg(all(feature = "no-wasm", feature = "gui"))]
-pub use gui::*;
+pub use gui::get_captcha_inner;
+#[cfg(all(feature = "no-wasm", feature = "gui", not(feature = "tui")))]
+pub use gui::{enroll_courses, enroll_courses_with_session};
 
 #[cfg(all(feature = "no-wasm", feature = "tui"))]
 pub use tui::*;
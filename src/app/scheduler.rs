@@ -0,0 +1,193 @@
+//! Scheduled enrollment around a batch's opening window
+//!
+//! [`Scheduler`] parses the batch `beginTime`/`endTime` (Asia/Shanghai),
+//! sleeps until shortly before the batch opens, warms up the session and then
+//! runs the [`EnrollmentEngine`] until the batch closes. Its progress goes
+//! out with the engine's [`EnrollEvent`]s.
+
+use crate::error::{ErrorKind, Result};
+use crate::model::structs::{BatchInfo, CourseInfo};
+use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeDelta, Utc};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
+
+use super::engine::{EnrollEvent, EnrollReport, EnrollmentEngine};
+use super::session::SessionManager;
+
+/// Asia/Shanghai has been UTC+8 without DST since 1991
+const SHANGHAI_OFFSET_SECS: i32 = 8 * 3600;
+const BATCH_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+pub fn shanghai() -> FixedOffset {
+    FixedOffset::east_opt(SHANGHAI_OFFSET_SECS).expect("UTC+8 is a valid offset")
+}
+
+/// Parse a batch timestamp such as `2025-08-20 13:00:00` in Asia/Shanghai time
+pub fn parse_batch_time(time: &str) -> Result<DateTime<FixedOffset>> {
+    let naive = NaiveDateTime::parse_from_str(time.trim(), BATCH_TIME_FORMAT)
+        .map_err(|e| ErrorKind::ParseError(format!("Invalid batch time {time:?}: {e}")))?;
+    naive
+        .and_local_timezone(shanghai())
+        .single()
+        .ok_or_else(|| ErrorKind::ParseError(format!("Ambiguous batch time {time:?}")).into())
+}
+
+/// Opening window of an elective batch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BatchWindow {
+    pub begin: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+impl BatchWindow {
+    pub fn from_batch(batch: &BatchInfo) -> Result<Self> {
+        Ok(Self {
+            begin: parse_batch_time(&batch.begin_time)?,
+            end: parse_batch_time(&batch.end_time)?,
+        })
+    }
+
+    pub fn has_ended(&self, now: DateTime<FixedOffset>) -> bool {
+        now >= self.end
+    }
+
+    pub fn is_open(&self, now: DateTime<FixedOffset>) -> bool {
+        self.begin <= now && now < self.end
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ScheduleConfig {
+    /// Start enrolling this long before `begin_time`
+    pub lead_time: Duration,
+    /// Refresh the session this long before `begin_time`
+    pub warm_up: Duration,
+    /// Stop the engine once `end_time` has passed
+    pub stop_at_end: bool,
+}
+
+impl Default for ScheduleConfig {
+    fn default() -> Self {
+        Self {
+            lead_time: Duration::from_secs(1),
+            warm_up: Duration::from_secs(30),
+            stop_at_end: true,
+        }
+    }
+}
+
+pub struct Scheduler {
+    session: Arc<SessionManager>,
+    config: ScheduleConfig,
+    /// Server clock minus local clock
+    clock_offset: TimeDelta,
+}
+
+impl Scheduler {
+    pub fn new(session: Arc<SessionManager>, config: ScheduleConfig) -> Self {
+        Self {
+            session,
+            config,
+            clock_offset: TimeDelta::zero(),
+        }
+    }
+
+    /// Correct the local clock by `offset` (server minus local)
+    pub fn with_clock_offset(mut self, offset: TimeDelta) -> Self {
+        self.clock_offset = offset;
+        self
+    }
+
+    pub fn session(&self) -> &Arc<SessionManager> {
+        &self.session
+    }
+
+    /// Current time on the server in Asia/Shanghai
+    pub fn server_now(&self) -> DateTime<FixedOffset> {
+        (Utc::now() + self.clock_offset).with_timezone(&shanghai())
+    }
+
+    /// Window of the batch the session is logged into
+    pub async fn window(&self) -> Result<BatchWindow> {
+        let session = self.session.current().await?;
+        let batch = session
+            .batch()
+            .ok_or_else(|| ErrorKind::CourseError(format!("未找到批次 {}", session.batch_id)))?;
+        BatchWindow::from_batch(batch)
+    }
+
+    /// Wait for the batch to open, then run `engine` until it finishes or the batch ends
+    pub async fn run(
+        &self,
+        engine: &EnrollmentEngine,
+        courses: &[CourseInfo],
+    ) -> Result<EnrollReport> {
+        let window = self.window().await?;
+        if window.has_ended(self.server_now()) {
+            return Err(ErrorKind::BatchEnded {
+                end_time: window.end.format(BATCH_TIME_FORMAT).to_string(),
+            }
+            .into());
+        }
+
+        let cancel = engine.cancellation();
+        let warm_up_at = before(window.begin, self.config.warm_up);
+        if self.sleep_until(warm_up_at, cancel).await {
+            engine.emit(EnrollEvent::WarmingUp);
+        }
+        // 等待时被取消，不再发出请求，直接结束
+        if cancel.is_cancelled() {
+            return engine.run(courses).await;
+        }
+        // 预热：校验令牌，必要时重新登录
+        self.session.get_courses().await?;
+
+        let start_at = before(window.begin, self.config.lead_time);
+        self.sleep_until(start_at, cancel).await;
+        engine.emit(EnrollEvent::Starting {
+            server_time: self.server_now().format(BATCH_TIME_FORMAT).to_string(),
+        });
+
+        let run = engine.run(courses);
+        tokio::pin!(run);
+        if !self.config.stop_at_end {
+            return run.await;
+        }
+
+        tokio::select! {
+            report = &mut run => report,
+            _ = self.sleep_until(window.end, cancel) => {
+                if !cancel.is_cancelled() {
+                    engine.emit(EnrollEvent::BatchEnded);
+                    cancel.cancel();
+                }
+                run.await
+            }
+        }
+    }
+
+    /// Sleep until `deadline` in server time or until `cancel` is cancelled,
+    /// returning whether any time passed
+    async fn sleep_until(
+        &self,
+        deadline: DateTime<FixedOffset>,
+        cancel: &CancellationToken,
+    ) -> bool {
+        match (deadline - self.server_now()).to_std() {
+            Ok(wait) if !wait.is_zero() => {
+                cancel.run_until_cancelled(tokio::time::sleep(wait)).await;
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// `time - duration`, saturating at the earliest representable time
+fn before(time: DateTime<FixedOffset>, duration: Duration) -> DateTime<FixedOffset> {
+    TimeDelta::from_std(duration)
+        .ok()
+        .and_then(|delta| time.checked_sub_signed(delta))
+        .unwrap_or_else(|| DateTime::<Utc>::MIN_UTC.with_timezone(&shanghai()))
+}
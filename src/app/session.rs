@@ -0,0 +1,327 @@
+//! Session manager - owns credentials and transparently re-logs in
+//!
+//! [`SessionManager`] holds the current token and batch. When any request is
+//! rejected with 401 it re-runs the captcha login and `set_batch` flow once,
+//! then retries the request with the fresh token. Re-logins are reported as
+//! [`SessionEvent`]s for the front end to show.
+
+use crate::{
+    client::request::NoWasmClient,
+    error::{ErrorKind, Result},
+    interface::RequestApi,
+    model::dtos::{CourseQueryParams, CourseSelectParams},
+    model::responses::ApiResponse,
+    model::structs::{BatchInfo, BatchSelector, CourseInfo},
+};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::{Mutex as TokioMutex, RwLock, mpsc};
+
+use super::captcha::CaptchaSolver;
+use super::events::Subscribers;
+use super::request::{get_courses, login, set_batch};
+use super::store::SavedSession;
+
+/// How many captchas to try before giving up on a login
+const MAX_CAPTCHA_ATTEMPTS: usize = 3;
+
+#[derive(Clone)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"***")
+            .finish()
+    }
+}
+
+/// A logged in session with its batch selected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Session {
+    pub token: String,
+    pub batch_id: String,
+    #[serde(default)]
+    pub batch_list: Vec<BatchInfo>,
+    /// Incremented on every login, used to coalesce concurrent refreshes
+    #[serde(skip)]
+    generation: u64,
+}
+
+impl Session {
+    /// Entry of `batch_list` for the selected batch
+    pub fn batch(&self) -> Option<&BatchInfo> {
+        self.batch_list.iter().find(|b| b.code == self.batch_id)
+    }
+}
+
+/// Login progress reported by [`SessionManager`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The token expired mid-run and the session logs in again
+    Relogin,
+    /// The captcha answer was wrong and another captcha is being solved
+    CaptchaRejected { attempt: usize },
+}
+
+pub struct SessionManager {
+    client: NoWasmClient,
+    credentials: Option<Credentials>,
+    batch: BatchSelector,
+    solver: Option<Arc<dyn CaptchaSolver>>,
+    session: RwLock<Option<Session>>,
+    relogin: TokioMutex<()>,
+    subscribers: Subscribers<SessionEvent>,
+}
+
+impl SessionManager {
+    /// Session manager able to log in again with `credentials`
+    pub fn new(
+        client: NoWasmClient,
+        credentials: Credentials,
+        batch: impl Into<BatchSelector>,
+        solver: Arc<dyn CaptchaSolver>,
+    ) -> Self {
+        Self {
+            client,
+            credentials: Some(credentials),
+            batch: batch.into(),
+            solver: Some(solver),
+            session: RwLock::new(None),
+            relogin: TokioMutex::new(()),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Session manager wrapping an existing token; it cannot re-login
+    pub fn with_token(client: NoWasmClient, token: &str, batch_id: &str) -> Self {
+        let session = Session {
+            token: token.to_string(),
+            batch_id: batch_id.to_string(),
+            batch_list: Vec::new(),
+            generation: 0,
+        };
+        Self {
+            client,
+            credentials: None,
+            batch: BatchSelector::Code(batch_id.to_string()),
+            solver: None,
+            session: RwLock::new(Some(session)),
+            relogin: TokioMutex::new(()),
+            subscribers: Subscribers::default(),
+        }
+    }
+
+    /// Start from a previously saved session instead of logging in
+    pub fn with_session(mut self, session: Session) -> Self {
+        self.session = RwLock::new(Some(session));
+        self
+    }
+
+    pub fn client(&self) -> &NoWasmClient {
+        &self.client
+    }
+
+    /// Receive the login progress from now on
+    pub fn subscribe(&self) -> mpsc::UnboundedReceiver<SessionEvent> {
+        self.subscribers.subscribe()
+    }
+
+    /// Current session, logging in first if there is none yet
+    pub async fn current(&self) -> Result<Session> {
+        if let Some(session) = self.session.read().await.clone() {
+            return Ok(session);
+        }
+
+        let _guard = self.relogin.lock().await;
+        if let Some(session) = self.session.read().await.clone() {
+            return Ok(session);
+        }
+        self.login_locked(0).await
+    }
+
+    /// Replace `stale` with a fresh session unless another task already did
+    pub async fn refresh(&self, stale: &Session) -> Result<Session> {
+        let _guard = self.relogin.lock().await;
+        if let Some(session) = self.session.read().await.clone()
+            && session.generation != stale.generation
+        {
+            return Ok(session);
+        }
+        self.subscribers.emit(SessionEvent::Relogin);
+        self.login_locked(stale.generation + 1).await
+    }
+
+    /// Run `op` with the current session, re-logging in once on token expiry
+    pub async fn run<T, F, Fut>(&self, op: F) -> Result<T>
+    where
+        F: Fn(Session) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let session = self.current().await?;
+        match op(session.clone()).await {
+            Err(e) if e.is_token_expired() => {
+                let session = self.refresh(&session).await?;
+                op(session).await
+            }
+            result => result,
+        }
+    }
+
+    /// Session and cookies to save for a later run, `None` before logging in
+    pub async fn snapshot(&self) -> Option<SavedSession> {
+        let session = self.session.read().await.clone()?;
+        Some(SavedSession {
+            username: self.credentials.as_ref().map(|c| c.username.clone()),
+            session,
+            cookies: self.client.cookies(),
+        })
+    }
+
+    /// Continue a saved session if the server still accepts it
+    ///
+    /// Returns `false` and forgets the session when it has expired, so the
+    /// next request logs in again. The session is forgotten as well when it
+    /// cannot be checked.
+    pub async fn resume(&self, saved: SavedSession) -> Result<bool> {
+        self.client.restore_cookies(saved.cookies)?;
+        let generation = self.current_generation().await;
+        *self.session.write().await = Some(Session {
+            generation: generation + 1,
+            ..saved.session
+        });
+
+        let valid = self.validate().await;
+        if !matches!(valid, Ok(true)) {
+            *self.session.write().await = None;
+        }
+        valid
+    }
+
+    /// Check the current session with a cheap authenticated request, without re-login
+    pub async fn validate(&self) -> Result<bool> {
+        let Some(session) = self.session.read().await.clone() else {
+            return Ok(false);
+        };
+        let params = CourseQueryParams {
+            token: session.token,
+            batch_id: session.batch_id,
+        };
+        match self
+            .client
+            .get_selected_courses(params)
+            .await
+            .and_then(|resp| resp.into_data())
+        {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_token_expired() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn current_generation(&self) -> u64 {
+        self.session
+            .read()
+            .await
+            .as_ref()
+            .map_or(0, |s| s.generation)
+    }
+
+    /// Select a course, treating a 401 reply as token expiry
+    pub async fn select_course(&self, course: &CourseInfo) -> Result<ApiResponse> {
+        self.run(|session| async move {
+            let resp = self
+                .client
+                .select_course(CourseSelectParams {
+                    token: session.token,
+                    batch_id: session.batch_id,
+                    class_type: course.teaching_class_type.clone().unwrap_or_default(),
+                    class_id: course.JXBID.clone(),
+                    secret_val: course.secret_val.clone().unwrap_or_default(),
+                })
+                .await?;
+            if resp.code == 401 {
+                return Err(ErrorKind::TokenExpired.into());
+            }
+            Ok(resp)
+        })
+        .await
+    }
+
+    /// Selected and favorite courses of the current batch
+    pub async fn get_courses(&self) -> Result<(Vec<CourseInfo>, Vec<CourseInfo>)> {
+        self.run(|session| async move {
+            get_courses(&self.client, &session.token, &session.batch_id).await
+        })
+        .await
+    }
+
+    /// Selected courses of the current batch
+    pub async fn get_selected_courses(&self) -> Result<Vec<CourseInfo>> {
+        self.run(|session| async move {
+            let params = CourseQueryParams {
+                token: session.token,
+                batch_id: session.batch_id,
+            };
+            self.client.get_selected_courses(params).await?.into_data()
+        })
+        .await
+    }
+
+    /// Favorite courses of the current batch, with fresh `secretVal`s
+    pub async fn get_favorite_courses(&self) -> Result<Vec<CourseInfo>> {
+        self.run(|session| async move {
+            let params = CourseQueryParams {
+                token: session.token,
+                batch_id: session.batch_id,
+            };
+            self.client.get_favorite_courses(params).await?.into_data()
+        })
+        .await
+    }
+
+    /// Log in and select the batch; the caller must hold `relogin`
+    async fn login_locked(&self, generation: u64) -> Result<Session> {
+        let (Some(credentials), Some(solver)) = (&self.credentials, &self.solver) else {
+            return Err(ErrorKind::TokenExpired.into());
+        };
+
+        let mut attempt = 1;
+        let (token, batch_list) = loop {
+            match login(
+                &self.client,
+                &credentials.username,
+                &credentials.password,
+                solver.as_ref(),
+            )
+            .await
+            {
+                Ok(result) => break result,
+                Err(e)
+                    if matches!(e.kind(), ErrorKind::CaptchaWrong)
+                        && attempt < MAX_CAPTCHA_ATTEMPTS =>
+                {
+                    self.subscribers
+                        .emit(SessionEvent::CaptchaRejected { attempt });
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        };
+
+        let batch_id = set_batch(&self.client, &token, &batch_list, self.batch.clone()).await?;
+        let session = Session {
+            token,
+            batch_id,
+            batch_list,
+            generation,
+        };
+        *self.session.write().await = Some(session.clone());
+        Ok(session)
+    }
+}
@@ -0,0 +1,96 @@
+//! Session persistence between runs
+//!
+//! [`SessionStore`] saves the token, the selected batch and the client's
+//! cookies as JSON, so the next run can
+//! [`resume`](super::session::SessionManager::resume) without a captcha.
+//!
+//! On Unix the file is readable by the current user only. Other platforms
+//! create it with the default permissions of its directory, so it is only
+//! as private as the directory it is saved in.
+
+use crate::error::Result;
+use cookie_store::Cookie;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use super::session::Session;
+
+/// Everything needed to resume a login
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedSession {
+    /// Account the session belongs to, `None` if unknown
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(flatten)]
+    pub session: Session,
+    #[serde(default)]
+    pub cookies: Vec<Cookie<'static>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionStore {
+    path: PathBuf,
+}
+
+impl SessionStore {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Saved session, `None` if nothing has been saved yet
+    pub fn load(&self) -> Result<Option<SavedSession>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Replace the saved session atomically
+    pub fn save(&self, saved: &SavedSession) -> Result<()> {
+        write_secret(&self.path, &serde_json::to_vec_pretty(saved)?)
+    }
+
+    pub fn clear(&self) -> Result<()> {
+        match std::fs::remove_file(&self.path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Atomically replace `path` with `content`, readable by the owner only on Unix
+pub(crate) fn write_secret(path: &Path, content: &[u8]) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    {
+        let mut file = create_secret(&tmp)?;
+        file.write_all(content)?;
+        file.sync_all()?;
+    }
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Create or truncate `path`, readable and writable by the owner only on Unix
+///
+/// Elsewhere the file keeps the default permissions.
+fn create_secret(path: &Path) -> std::io::Result<std::fs::File> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        let file = options.open(path)?;
+        // 文件已存在时 mode 不生效，需要显式收紧权限
+        file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        Ok(file)
+    }
+    #[cfg(not(unix))]
+    options.open(path)
+}
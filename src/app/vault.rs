@@ -0,0 +1,212 @@
+//! Encrypted credential storage
+//!
+//! [`CredentialVault`] keeps account passwords in a file encrypted with
+//! AES-256-GCM under a key derived from a passphrase with Argon2id, so
+//! passwords never have to appear on the command line or in a config file.
+//! Usernames are stored in clear so the accounts can be listed without the
+//! passphrase; each password is bound to its username and cannot be moved to
+//! another entry.
+
+use crate::error::{ErrorKind, Result};
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use super::store::write_secret;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+/// Encrypted with the key to tell a wrong passphrase from a corrupted entry
+const CHECK_PLAINTEXT: &[u8] = b"funky-lesson-vault";
+const CHECK_AAD: &[u8] = b"check";
+
+/// Argon2id cost parameters, stored in the vault so they can change later
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KdfParams {
+    /// Memory in KiB
+    pub m_cost: u32,
+    pub t_cost: u32,
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        Self {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+impl KdfParams {
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<Key<Aes256Gcm>> {
+        let params = Params::new(self.m_cost, self.t_cost, self.p_cost, Some(32))
+            .map_err(|e| ErrorKind::ParseError(format!("Invalid KDF params: {e}")))?;
+        let mut key = Key::<Aes256Gcm>::default();
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| ErrorKind::ParseError(format!("Key derivation failed: {e}")))?;
+        Ok(key)
+    }
+}
+
+/// Nonce and ciphertext, both base64
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sealed {
+    nonce: String,
+    data: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct VaultFile {
+    kdf: KdfParams,
+    salt: String,
+    check: Sealed,
+    #[serde(default)]
+    entries: BTreeMap<String, Sealed>,
+}
+
+impl VaultFile {
+    fn read(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// An unlocked vault
+pub struct CredentialVault {
+    path: PathBuf,
+    file: VaultFile,
+    cipher: Aes256Gcm,
+}
+
+impl std::fmt::Debug for CredentialVault {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CredentialVault")
+            .field("path", &self.path)
+            .field("usernames", &self.file.entries.keys().collect::<Vec<_>>())
+            .finish_non_exhaustive()
+    }
+}
+
+impl CredentialVault {
+    /// Unlock the vault at `path`, or start an empty one if it does not exist
+    ///
+    /// Fails with [`ErrorKind::VaultLocked`] if `passphrase` is wrong.
+    pub fn open(path: impl Into<PathBuf>, passphrase: &str) -> Result<Self> {
+        let path = path.into();
+        match VaultFile::read(&path)? {
+            Some(file) => {
+                let salt = base64_simd::STANDARD.decode_to_vec(&file.salt)?;
+                let cipher = Aes256Gcm::new(&file.kdf.derive_key(passphrase, &salt)?);
+                open_sealed(&cipher, &file.check, CHECK_AAD)?;
+                Ok(Self { path, file, cipher })
+            }
+            None => Self::create(path, passphrase, KdfParams::default()),
+        }
+    }
+
+    /// Start an empty vault, replacing any existing file on [`save`](Self::save)
+    pub fn create(path: impl Into<PathBuf>, passphrase: &str, kdf: KdfParams) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        OsRng.fill_bytes(&mut salt);
+        let cipher = Aes256Gcm::new(&kdf.derive_key(passphrase, &salt)?);
+        let check = seal(&cipher, CHECK_PLAINTEXT, CHECK_AAD)?;
+        Ok(Self {
+            path: path.into(),
+            file: VaultFile {
+                kdf,
+                salt: base64_simd::STANDARD.encode_to_string(salt),
+                check,
+                entries: BTreeMap::new(),
+            },
+            cipher,
+        })
+    }
+
+    /// Accounts stored at `path`, readable without the passphrase
+    pub fn list(path: impl AsRef<Path>) -> Result<Vec<String>> {
+        Ok(VaultFile::read(path.as_ref())?
+            .map(|file| file.entries.into_keys().collect())
+            .unwrap_or_default())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn usernames(&self) -> impl Iterator<Item = &str> {
+        self.file.entries.keys().map(String::as_str)
+    }
+
+    pub fn contains(&self, username: &str) -> bool {
+        self.file.entries.contains_key(username)
+    }
+
+    /// Decrypted password of `username`
+    pub fn get(&self, username: &str) -> Result<Option<String>> {
+        let Some(sealed) = self.file.entries.get(username) else {
+            return Ok(None);
+        };
+        let password = open_sealed(&self.cipher, sealed, username.as_bytes())?;
+        String::from_utf8(password)
+            .map(Some)
+            .map_err(|_| ErrorKind::VaultLocked.into())
+    }
+
+    /// Add or replace an account, returns `true` if it replaced one
+    pub fn insert(&mut self, username: &str, password: &str) -> Result<bool> {
+        let sealed = seal(&self.cipher, password.as_bytes(), username.as_bytes())?;
+        Ok(self
+            .file
+            .entries
+            .insert(username.to_string(), sealed)
+            .is_some())
+    }
+
+    /// Returns `true` if the account was stored
+    pub fn remove(&mut self, username: &str) -> bool {
+        self.file.entries.remove(username).is_some()
+    }
+
+    /// Write the vault to its file, readable by the owner only on Unix
+    pub fn save(&self) -> Result<()> {
+        write_secret(&self.path, &serde_json::to_vec_pretty(&self.file)?)
+    }
+}
+
+fn seal(cipher: &Aes256Gcm, plaintext: &[u8], aad: &[u8]) -> Result<Sealed> {
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let data = cipher
+        .encrypt(
+            &nonce,
+            Payload {
+                msg: plaintext,
+                aad,
+            },
+        )
+        .map_err(|_| ErrorKind::ParseError("Encryption failed".to_string()))?;
+    Ok(Sealed {
+        nonce: base64_simd::STANDARD.encode_to_string(nonce),
+        data: base64_simd::STANDARD.encode_to_string(data),
+    })
+}
+
+fn open_sealed(cipher: &Aes256Gcm, sealed: &Sealed, aad: &[u8]) -> Result<Vec<u8>> {
+    let nonce = base64_simd::STANDARD.decode_to_vec(&sealed.nonce)?;
+    if nonce.len() != NONCE_LEN {
+        return Err(ErrorKind::VaultLocked.into());
+    }
+    let data = base64_simd::STANDARD.decode_to_vec(&sealed.data)?;
+    cipher
+        .decrypt(Nonce::from_slice(&nonce), Payload { msg: &data, aad })
+        .map_err(|_| ErrorKind::VaultLocked.into())
+}
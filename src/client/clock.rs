@@ -0,0 +1,123 @@
+//! Server clock estimation from HTTP `Date` headers
+//!
+//! [`ClockSync::measure`] sends a few lightweight `HEAD` requests, pairs each
+//! `Date` header with the local send/receive times and keeps the sample with
+//! the smallest round trip, like NTP's minimum-delay filter. Failed samples
+//! are skipped as long as one succeeds.
+
+use crate::client::rate_limit::Endpoint;
+use crate::client::request::NoWasmClient;
+use crate::error::{ErrorKind, Result};
+use crate::interface::HttpClient;
+use chrono::{DateTime, TimeDelta, Utc};
+use std::time::{Duration, Instant};
+
+/// One `Date` header paired with the local request timing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSample {
+    /// Server clock minus local clock
+    pub offset: TimeDelta,
+    pub rtt: Duration,
+}
+
+impl ClockSample {
+    /// Sample from the local send/receive times and the server `Date`
+    ///
+    /// `Date` has second resolution, so the server time is assumed to be half
+    /// a second past the header value, at the midpoint of the round trip.
+    pub fn new(sent: DateTime<Utc>, rtt: Duration, server_date: DateTime<Utc>) -> Self {
+        let midpoint = sent + TimeDelta::from_std(rtt / 2).unwrap_or_default();
+        let server_time = server_date + TimeDelta::milliseconds(500);
+        Self {
+            offset: server_time - midpoint,
+            rtt,
+        }
+    }
+}
+
+/// Estimated offset between the server clock and the local clock
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockSync {
+    offset: TimeDelta,
+    rtt: Duration,
+    samples: usize,
+}
+
+impl ClockSync {
+    /// Sample the server clock `samples` times through `client`
+    ///
+    /// Fails with the error of the last sample if no sample succeeds.
+    pub async fn measure(client: &NoWasmClient, samples: usize) -> Result<Self> {
+        let mut collected = Vec::with_capacity(samples);
+        let mut last_error = None;
+        for i in 0..samples.max(1) {
+            if i > 0 {
+                // 错开采样时间，避免总是落在同一秒内的相同位置
+                tokio::time::sleep(Duration::from_millis(fastrand::u64(20..200))).await;
+            }
+            // 个别请求超时或缺少 Date 时跳过，用其余样本估计
+            match Self::sample(client).await {
+                Ok(sample) => collected.push(sample),
+                Err(e) => last_error = Some(e),
+            }
+        }
+
+        Self::from_samples(&collected).ok_or_else(|| {
+            last_error.unwrap_or_else(|| {
+                ErrorKind::ParseError("No clock sample taken".to_string()).into()
+            })
+        })
+    }
+
+    /// Keep the sample with the smallest round trip
+    pub fn from_samples(samples: &[ClockSample]) -> Option<Self> {
+        let best = samples.iter().min_by_key(|s| s.rtt)?;
+        Some(Self {
+            offset: best.offset,
+            rtt: best.rtt,
+            samples: samples.len(),
+        })
+    }
+
+    /// Time one `HEAD` request to the index page
+    async fn sample(client: &NoWasmClient) -> Result<ClockSample> {
+        if let Some(limiter) = client.rate_limiter() {
+            limiter.acquire(Endpoint::Index).await;
+        }
+        let url = client.config().url(&client.config().endpoints.index);
+
+        let sent = Utc::now();
+        let started = Instant::now();
+        let resp = client.inner().head(url).send().await?;
+        let rtt = started.elapsed();
+
+        let server_date = resp
+            .headers()
+            .get(reqwest::header::DATE)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| DateTime::parse_from_rfc2822(v).ok());
+        let server_date = server_date.ok_or_else(|| {
+            ErrorKind::ParseError("Server response has no valid Date header".to_string())
+        })?;
+        Ok(ClockSample::new(sent, rtt, server_date.with_timezone(&Utc)))
+    }
+
+    /// Server clock minus local clock
+    pub fn offset(&self) -> TimeDelta {
+        self.offset
+    }
+
+    /// Round trip of the sample the offset was taken from
+    pub fn rtt(&self) -> Duration {
+        self.rtt
+    }
+
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// Current time on the server clock
+    pub fn server_now(&self) -> DateTime<Utc> {
+        Utc::now() + self.offset
+    }
+}
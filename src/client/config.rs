@@ -0,0 +1,109 @@
+//! Server configuration - base URL and endpoint table
+//!
+//! All clients build their request URLs from a [`ServerConfig`], so the whole
+//! library can be pointed at a staging host, a local mock or a mirror.
+
+use serde::{Deserialize, Serialize};
+
+/// Default icourses host
+pub const DEFAULT_BASE_URL: &str = "https://icourses.jlu.edu.cn";
+
+/// Default CORS proxy used by the WASM client
+pub const DEFAULT_PROXY_BASE_URL: &str = "http://127.0.0.1:3030/api/proxy";
+
+/// Per-endpoint paths, relative to the base URL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Endpoints {
+    /// Index page containing `loginVue.loginForm.aesKey`
+    pub index: String,
+    /// Profile index page, used by the WASM client to fetch the AES key
+    pub profile_index: String,
+    pub captcha: String,
+    pub login: String,
+    /// `elective/user`, sets the current batch
+    pub elective_user: String,
+    /// `elective/grablessons`, visited after setting the batch
+    pub grablessons: String,
+    /// `elective/select`, lists selected courses
+    pub select: String,
+    /// `sc/clazz/list`, lists favorite courses
+    pub favorite_list: String,
+    /// `sc/clazz/addxk`, selects a course
+    pub add_course: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            index: "/".to_string(),
+            profile_index: "/xsxk/profile/index.html".to_string(),
+            captcha: "/xsxk/auth/captcha".to_string(),
+            login: "/xsxk/auth/login".to_string(),
+            elective_user: "/xsxk/elective/user".to_string(),
+            grablessons: "/xsxk/elective/grablessons".to_string(),
+            select: "/xsxk/elective/select".to_string(),
+            favorite_list: "/xsxk/sc/clazz/list".to_string(),
+            add_course: "/xsxk/sc/clazz/addxk".to_string(),
+        }
+    }
+}
+
+/// Base URL plus endpoint table supplied at client construction
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub base_url: String,
+    /// Proxy base URL for the WASM `*_proxy` methods
+    pub proxy_base_url: String,
+    pub endpoints: Endpoints,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_BASE_URL.to_string(),
+            proxy_base_url: DEFAULT_PROXY_BASE_URL.to_string(),
+            endpoints: Endpoints::default(),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Create a config for `base_url` with the default endpoint table
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Join an endpoint path onto the base URL
+    pub fn url(&self, path: &str) -> String {
+        join_url(&self.base_url, path)
+    }
+
+    /// Join an endpoint path onto the proxy base URL, dropping the `/xsxk` prefix
+    pub fn proxy_url(&self, path: &str) -> String {
+        join_url(
+            &self.proxy_base_url,
+            path.strip_prefix("/xsxk").unwrap_or(path),
+        )
+    }
+
+    /// URL of the grablessons page for `batch_id`, also used as `Referer`
+    pub fn grablessons_url(&self, batch_id: &str) -> String {
+        format!(
+            "{}?batchId={batch_id}",
+            self.url(&self.endpoints.grablessons)
+        )
+    }
+}
+
+fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
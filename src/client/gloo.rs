@@ -3,26 +3,51 @@
 //! This module provides HTTP functionality for WASM environments
 //! using the gloo_net crate for making HTTP requests via the browser's fetch API.
 
+use crate::client::config::ServerConfig;
 use crate::error::{ErrorKind, Result};
 use gloo_net::http::{Request, RequestBuilder};
+use serde::de::DeserializeOwned;
 use serde_json::{Value, json};
 use std::collections::HashMap;
 use web_sys::{RequestCredentials, RequestMode};
 
 use crate::interface::{HttpClient, RequestApi};
 use crate::model::dtos::{CourseQueryParams, CourseSelectParams, LoginParams};
+use crate::model::responses::{
+    ApiResponse, CaptchaData, CaptchaResponse, CourseListResponse, LoginResponse,
+};
 
 /// HTTP client for WASM environments using gloo_net
-#[derive(Debug, Clone)]
-pub struct WasmClient;
+#[derive(Debug, Clone, Default)]
+pub struct WasmClient {
+    config: ServerConfig,
+}
 
 impl HttpClient for WasmClient {
-    async fn new() -> Result<Self> {
-        Ok(Self)
+    async fn with_config(config: ServerConfig) -> Result<Self> {
+        Ok(Self { config })
+    }
+
+    fn config(&self) -> &ServerConfig {
+        &self.config
     }
 }
 
 impl WasmClient {
+    fn url(&self, path: &str) -> String {
+        self.config.url(path)
+    }
+
+    fn proxy_url(&self, path: &str) -> String {
+        self.config.proxy_url(path)
+    }
+
+    /// Deserialize a JSON response body, keeping serde's error location
+    async fn parse<T: DeserializeOwned>(resp: gloo_net::http::Response) -> Result<T> {
+        let text = resp.text().await?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
     /// Build a request with common headers and settings
     async fn build_request(method: &str, url: &str) -> RequestBuilder {
         let mut builder = match method {
@@ -42,7 +67,9 @@ impl WasmClient {
     }
 
     /// Handle JSON response with error checking
-    async fn handle_json_response(resp: gloo_net::http::Response) -> Result<Value> {
+    async fn handle_json_response<T: DeserializeOwned>(
+        resp: gloo_net::http::Response,
+    ) -> Result<T> {
         let status = resp.ok();
         let text = resp.text().await?;
 
@@ -55,7 +82,7 @@ impl WasmClient {
                 if let Some(error) = json.get("error") {
                     return Err(ErrorKind::ParseError(format!("Server error: {}", error)).into());
                 }
-                Ok(json)
+                Ok(serde_json::from_value(json)?)
             }
             Err(_) => Err(ErrorKind::ParseError(format!("Invalid JSON response: {}", text)).into()),
         }
@@ -64,9 +91,9 @@ impl WasmClient {
 
 impl RequestApi for WasmClient {
     async fn get_aes_key(&self) -> Result<Vec<u8>> {
-        let index_url = "https://icourses.jlu.edu.cn/xsxk/profile/index.html";
+        let index_url = self.url(&self.config.endpoints.profile_index);
 
-        let resp = Request::get(index_url)
+        let resp = Request::get(&index_url)
             .header("Accept", "text/html,application/xhtml+xml,application/xml;q=0.9,image/webp,*/*;q=0.8")
             .header("Accept-Language", "zh-CN,zh;q=0.9,en;q=0.8")
             .header("User-Agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/91.0.4472.124 Safari/537.36")
@@ -96,36 +123,24 @@ impl RequestApi for WasmClient {
                 })
             })
             .flatten()
-            .ok_or_else(|| ErrorKind::ParseError("Failed to extract AES key".to_string()))?;
+            .ok_or(ErrorKind::AesKeyNotFound)?;
 
         Ok(key)
     }
 
-    async fn get_captcha(&self) -> Result<(String, String)> {
-        let captcha_url = "https://icourses.jlu.edu.cn/xsxk/auth/captcha";
-        let resp = Request::post(captcha_url)
+    async fn get_captcha(&self) -> Result<CaptchaData> {
+        let captcha_url = self.url(&self.config.endpoints.captcha);
+        let resp = Request::post(&captcha_url)
             .mode(RequestMode::Cors)
             .credentials(RequestCredentials::SameOrigin)
             .send()
             .await?;
 
-        let captcha_data = resp.json::<Value>().await?;
-
-        let uuid = captcha_data["data"]["uuid"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha uuid".to_string()))?
-            .to_string();
-
-        let captcha = captcha_data["data"]["captcha"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha data".to_string()))?
-            .to_string();
-
-        Ok((uuid, captcha))
+        Self::parse::<CaptchaResponse>(resp).await?.into_data()
     }
 
-    async fn send_login_request(&self, params: LoginParams) -> Result<Value> {
-        let login_url = "https://icourses.jlu.edu.cn/xsxk/auth/login";
+    async fn send_login_request(&self, params: LoginParams) -> Result<LoginResponse> {
+        let login_url = self.url(&self.config.endpoints.login);
 
         let mut query_params = HashMap::new();
         query_params.insert("loginname", params.username);
@@ -133,19 +148,19 @@ impl RequestApi for WasmClient {
         query_params.insert("captcha", params.captcha);
         query_params.insert("uuid", params.uuid);
 
-        let resp = Request::post(login_url).query(query_params).send().await?;
+        let resp = Request::post(&login_url).query(query_params).send().await?;
 
-        resp.json::<Value>().await.map_err(Into::into)
+        Self::parse(resp).await
     }
 
-    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/elective/user";
+    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<ApiResponse> {
+        let url = self.url(&self.config.endpoints.elective_user);
         let mut params = HashMap::new();
         params.insert("batchId", batch_id);
 
         log::debug!("Sending request to {} with token: {}", url, token);
 
-        let resp = Request::post(url)
+        let resp = Request::post(&url)
             .mode(RequestMode::NoCors)
             .header("Authorization", token)
             .query(params)
@@ -157,49 +172,53 @@ impl RequestApi for WasmClient {
         log::debug!("Set Batch Response headers: {:?}", resp.headers());
 
         // Return a success response since direct response parsing might fail in WASM
-        Ok(json!({"code": 200, "status": "sent"}))
+        Ok(ApiResponse {
+            code: 200,
+            msg: "sent".to_string(),
+            data: None,
+        })
     }
 
-    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/elective/select";
+    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse> {
+        let url = self.url(&self.config.endpoints.select);
 
-        let resp = Request::post(url)
+        let resp = Request::post(&url)
             .header("Authorization", &params.token)
             .header("batchId", &params.batch_id)
             .send()
             .await?;
 
-        resp.json::<Value>().await.map_err(Into::into)
+        Self::parse(resp).await
     }
 
-    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/sc/clazz/list";
+    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse> {
+        let url = self.url(&self.config.endpoints.favorite_list);
 
-        let resp = Request::post(url)
+        let resp = Request::post(&url)
             .header("Authorization", &params.token)
             .header("batchId", &params.batch_id)
             .send()
             .await?;
 
-        resp.json::<Value>().await.map_err(Into::into)
+        Self::parse(resp).await
     }
 
-    async fn select_course(&self, params: CourseSelectParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/sc/clazz/addxk";
+    async fn select_course(&self, params: CourseSelectParams) -> Result<ApiResponse> {
+        let url = self.url(&self.config.endpoints.add_course);
 
         let mut query_params = HashMap::new();
         query_params.insert("clazzType", params.class_type);
         query_params.insert("clazzId", params.class_id);
         query_params.insert("secretVal", params.secret_val);
 
-        let resp = Request::post(url)
+        let resp = Request::post(&url)
             .header("Authorization", &params.token)
             .header("batchId", &params.batch_id)
             .query(query_params)
             .send()
             .await?;
 
-        resp.json::<Value>().await.map_err(Into::into)
+        Self::parse(resp).await
     }
 }
 
@@ -207,9 +226,9 @@ impl RequestApi for WasmClient {
 impl WasmClient {
     /// Get AES key via proxy server
     pub async fn get_aes_key_proxy(&self) -> Result<Vec<u8>> {
-        let url = "http://127.0.0.1:3030/api/proxy/profile/index.html";
+        let url = self.proxy_url(&self.config.endpoints.profile_index);
 
-        let resp = Self::build_request("GET", url).await.send().await?;
+        let resp = Self::build_request("GET", &url).await.send().await?;
 
         let html = resp.text().await?;
 
@@ -226,53 +245,43 @@ impl WasmClient {
                 })
             })
             .flatten()
-            .ok_or_else(|| ErrorKind::ParseError("Failed to extract AES key".to_string()))?;
+            .ok_or(ErrorKind::AesKeyNotFound)?;
 
         Ok(key)
     }
 
     /// Get captcha via proxy server
-    pub async fn get_captcha_proxy(&self) -> Result<(String, String)> {
-        let url = "http://127.0.0.1:3030/api/proxy/auth/captcha";
+    pub async fn get_captcha_proxy(&self) -> Result<CaptchaData> {
+        let url = self.proxy_url(&self.config.endpoints.captcha);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/auth/captcha"
+            "original_url": self.url(&self.config.endpoints.captcha)
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .json(&body)?
             .send()
             .await?;
 
-        let json = Self::handle_json_response(resp).await?;
-
-        let uuid = json["data"]["uuid"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha uuid".to_string()))?
-            .to_string();
-
-        let captcha = json["data"]["captcha"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha data".to_string()))?
-            .to_string();
-
-        Ok((uuid, captcha))
+        Self::handle_json_response::<CaptchaResponse>(resp)
+            .await?
+            .into_data()
     }
 
     /// Send login request via proxy server
-    pub async fn send_login_request_proxy(&self, params: LoginParams) -> Result<Value> {
-        let url = "http://127.0.0.1:3030/api/proxy/auth/login";
+    pub async fn send_login_request_proxy(&self, params: LoginParams) -> Result<LoginResponse> {
+        let url = self.proxy_url(&self.config.endpoints.login);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/auth/login",
+            "original_url": self.url(&self.config.endpoints.login),
             "loginname": params.username,
             "password": params.encrypted_password,
             "captcha": params.captcha,
             "uuid": params.uuid
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .json(&body)?
             .send()
@@ -282,15 +291,15 @@ impl WasmClient {
     }
 
     /// Set batch via proxy server
-    pub async fn set_batch_proxy(&self, batch_id: &str, token: &str) -> Result<Value> {
-        let url = "http://127.0.0.1:3030/api/proxy/elective/user";
+    pub async fn set_batch_proxy(&self, batch_id: &str, token: &str) -> Result<ApiResponse> {
+        let url = self.proxy_url(&self.config.endpoints.elective_user);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/elective/user",
+            "original_url": self.url(&self.config.endpoints.elective_user),
             "batch_id": batch_id
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .header("Authorization", token)
             .json(&body)?
@@ -299,7 +308,8 @@ impl WasmClient {
 
         // await client.HttpGetAsync("xsxk/elective/grablessons?batchId=" + batch.batchId);
         let get_url = format!(
-            "http://127.0.0.1:3030/api/proxy/elective/grablessons?batchId={}",
+            "{}?batchId={}",
+            self.proxy_url(&self.config.endpoints.grablessons),
             batch_id
         );
         Self::build_request("GET", &get_url)
@@ -313,15 +323,18 @@ impl WasmClient {
     }
 
     /// Get selected courses via proxy server
-    pub async fn get_selected_courses_proxy(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "http://127.0.0.1:3030/api/proxy/elective/select";
+    pub async fn get_selected_courses_proxy(
+        &self,
+        params: CourseQueryParams,
+    ) -> Result<CourseListResponse> {
+        let url = self.proxy_url(&self.config.endpoints.select);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/elective/select",
+            "original_url": self.url(&self.config.endpoints.select),
             "batch_id": params.batch_id
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .header("Authorization", &params.token)
             .json(&body)?
@@ -332,14 +345,17 @@ impl WasmClient {
     }
 
     /// Get favorite courses via proxy server
-    pub async fn get_favorite_courses_proxy(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "http://127.0.0.1:3030/api/proxy/sc/clazz/list";
+    pub async fn get_favorite_courses_proxy(
+        &self,
+        params: CourseQueryParams,
+    ) -> Result<CourseListResponse> {
+        let url = self.proxy_url(&self.config.endpoints.favorite_list);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/sc/clazz/list",
+            "original_url": self.url(&self.config.endpoints.favorite_list),
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .header("Authorization", &params.token)
             .header("BatchId", &params.batch_id)
@@ -351,18 +367,18 @@ impl WasmClient {
     }
 
     /// Select course via proxy server
-    pub async fn select_course_proxy(&self, params: CourseSelectParams) -> Result<Value> {
-        let url = "http://127.0.0.1:3030/api/proxy/sc/clazz/addxk";
+    pub async fn select_course_proxy(&self, params: CourseSelectParams) -> Result<ApiResponse> {
+        let url = self.proxy_url(&self.config.endpoints.add_course);
 
         let body = json!({
-            "original_url": "https://icourses.jlu.edu.cn/xsxk/sc/clazz/addxk",
+            "original_url": self.url(&self.config.endpoints.add_course),
             "batch_id": params.batch_id,
             "class_type": params.class_type,
             "class_id": params.class_id,
             "secret_val": params.secret_val
         });
 
-        let resp = Self::build_request("POST", url)
+        let resp = Self::build_request("POST", &url)
             .await
             .header("Authorization", &params.token)
             .json(&body)?
@@ -379,22 +395,22 @@ pub async fn create_client() -> Result<()> {
 }
 
 pub async fn get_aes_key() -> Result<Vec<u8>> {
-    let client = WasmClient;
+    let client = WasmClient::default();
     client.get_aes_key().await
 }
 
 pub async fn get_aes_key_proxy() -> Result<Vec<u8>> {
-    let client = WasmClient;
+    let client = WasmClient::default();
     client.get_aes_key_proxy().await
 }
 
-pub async fn get_captcha() -> Result<(String, String)> {
-    let client = WasmClient;
+pub async fn get_captcha() -> Result<CaptchaData> {
+    let client = WasmClient::default();
     client.get_captcha().await
 }
 
-pub async fn get_captcha_proxy() -> Result<(String, String)> {
-    let client = WasmClient;
+pub async fn get_captcha_proxy() -> Result<CaptchaData> {
+    let client = WasmClient::default();
     client.get_captcha_proxy().await
 }
 
@@ -403,8 +419,8 @@ pub async fn send_login_request(
     encrypted_password: &str,
     captcha: &str,
     uuid: &str,
-) -> Result<Value> {
-    let client = WasmClient;
+) -> Result<LoginResponse> {
+    let client = WasmClient::default();
     let params = LoginParams {
         username: username.to_string(),
         encrypted_password: encrypted_password.to_string(),
@@ -419,8 +435,8 @@ pub async fn send_login_request_proxy(
     encrypted_password: &str,
     captcha: &str,
     uuid: &str,
-) -> Result<Value> {
-    let client = WasmClient;
+) -> Result<LoginResponse> {
+    let client = WasmClient::default();
     let params = LoginParams {
         username: username.to_string(),
         encrypted_password: encrypted_password.to_string(),
@@ -430,18 +446,18 @@ pub async fn send_login_request_proxy(
     client.send_login_request_proxy(params).await
 }
 
-pub async fn set_batch(batch_id: &str, token: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn set_batch(batch_id: &str, token: &str) -> Result<ApiResponse> {
+    let client = WasmClient::default();
     client.set_batch(batch_id, token).await
 }
 
-pub async fn set_batch_proxy(batch_id: &str, token: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn set_batch_proxy(batch_id: &str, token: &str) -> Result<ApiResponse> {
+    let client = WasmClient::default();
     client.set_batch_proxy(batch_id, token).await
 }
 
-pub async fn get_selected_courses(token: &str, batch_id: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn get_selected_courses(token: &str, batch_id: &str) -> Result<CourseListResponse> {
+    let client = WasmClient::default();
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -449,8 +465,8 @@ pub async fn get_selected_courses(token: &str, batch_id: &str) -> Result<Value>
     client.get_selected_courses(params).await
 }
 
-pub async fn get_selected_courses_proxy(token: &str, batch_id: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn get_selected_courses_proxy(token: &str, batch_id: &str) -> Result<CourseListResponse> {
+    let client = WasmClient::default();
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -458,8 +474,8 @@ pub async fn get_selected_courses_proxy(token: &str, batch_id: &str) -> Result<V
     client.get_selected_courses_proxy(params).await
 }
 
-pub async fn get_favorite_courses(token: &str, batch_id: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn get_favorite_courses(token: &str, batch_id: &str) -> Result<CourseListResponse> {
+    let client = WasmClient::default();
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -467,8 +483,8 @@ pub async fn get_favorite_courses(token: &str, batch_id: &str) -> Result<Value>
     client.get_favorite_courses(params).await
 }
 
-pub async fn get_favorite_courses_proxy(token: &str, batch_id: &str) -> Result<Value> {
-    let client = WasmClient;
+pub async fn get_favorite_courses_proxy(token: &str, batch_id: &str) -> Result<CourseListResponse> {
+    let client = WasmClient::default();
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -482,8 +498,8 @@ pub async fn select_course(
     class_type: &str,
     class_id: &str,
     secret_val: &str,
-) -> Result<Value> {
-    let client = WasmClient;
+) -> Result<ApiResponse> {
+    let client = WasmClient::default();
     let params = CourseSelectParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -500,8 +516,8 @@ pub async fn select_course_proxy(
     class_type: &str,
     class_id: &str,
     secret_val: &str,
-) -> Result<Value> {
-    let client = WasmClient;
+) -> Result<ApiResponse> {
+    let client = WasmClient::default();
     let params = CourseSelectParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -3,6 +3,13 @@
 //! This module provides a unified interface for making HTTP requests while
 //! supporting different implementations for WASM (gloo_net) and no-WASM (reqwest) environments.
 
+pub mod config;
+pub use config::ServerConfig;
+
+#[cfg(feature = "no-wasm")]
+pub mod clock;
+#[cfg(feature = "no-wasm")]
+pub mod rate_limit;
 #[cfg(feature = "no-wasm")]
 pub mod request;
 
@@ -0,0 +1,179 @@
+//! Token-bucket rate limiting shared by every request of a client
+//!
+//! A [`RateLimiter`] holds an optional global bucket plus one bucket per
+//! [`Endpoint`]. Each request takes a token from both before it is sent and
+//! the time spent waiting is recorded in [`ThrottleMetrics`].
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex as StdMutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Server endpoints, matching the fields of [`Endpoints`](super::config::Endpoints)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Endpoint {
+    Index,
+    Captcha,
+    Login,
+    ElectiveUser,
+    Grablessons,
+    Select,
+    FavoriteList,
+    AddCourse,
+}
+
+/// Sustained rate and burst size of a bucket
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Requests per second
+    pub rps: f64,
+    /// Requests allowed back to back after an idle period
+    #[serde(default = "default_burst")]
+    pub burst: u32,
+}
+
+fn default_burst() -> u32 {
+    1
+}
+
+impl RateLimit {
+    pub fn per_second(rps: f64) -> Self {
+        Self {
+            rps,
+            burst: default_burst(),
+        }
+    }
+
+    pub fn with_burst(mut self, burst: u32) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    last: Instant,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    limit: RateLimit,
+    state: StdMutex<BucketState>,
+}
+
+impl Bucket {
+    fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            state: StdMutex::new(BucketState {
+                tokens: limit.burst as f64,
+                last: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token, returning how long the caller has to wait for it
+    ///
+    /// Tokens may go negative so concurrent callers queue up in order.
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = now.saturating_duration_since(state.last).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.limit.rps).min(self.limit.burst as f64);
+        state.last = now;
+
+        state.tokens -= 1.0;
+        if state.tokens >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-state.tokens / self.limit.rps)
+        }
+    }
+}
+
+/// Throttling counters of one endpoint or of the whole limiter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ThrottleStats {
+    pub requests: u64,
+    /// Requests that had to wait for a token
+    pub throttled: u64,
+    pub total_wait: Duration,
+    pub max_wait: Duration,
+}
+
+impl ThrottleStats {
+    fn record(&mut self, wait: Duration) {
+        self.requests += 1;
+        if !wait.is_zero() {
+            self.throttled += 1;
+            self.total_wait += wait;
+            self.max_wait = self.max_wait.max(wait);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ThrottleMetrics {
+    pub total: ThrottleStats,
+    pub endpoints: HashMap<Endpoint, ThrottleStats>,
+}
+
+/// Shared async rate limiter with a global ceiling and per-endpoint limits
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    global: Option<Bucket>,
+    endpoints: HashMap<Endpoint, Bucket>,
+    metrics: StdMutex<ThrottleMetrics>,
+}
+
+impl RateLimiter {
+    /// Limiter with an optional global ceiling; non-positive rates are ignored
+    pub fn new(global: Option<RateLimit>) -> Self {
+        Self {
+            global: global.filter(|l| l.rps > 0.0).map(Bucket::new),
+            ..Self::default()
+        }
+    }
+
+    /// Add a limit for a single endpoint on top of the global one
+    pub fn with_endpoint(mut self, endpoint: Endpoint, limit: RateLimit) -> Self {
+        if limit.rps > 0.0 {
+            self.endpoints.insert(endpoint, Bucket::new(limit));
+        } else {
+            self.endpoints.remove(&endpoint);
+        }
+        self
+    }
+
+    /// Wait until a request to `endpoint` is allowed, returning the time waited
+    pub async fn acquire(&self, endpoint: Endpoint) -> Duration {
+        let now = Instant::now();
+        let global_wait = self
+            .global
+            .as_ref()
+            .map_or(Duration::ZERO, |b| b.reserve(now));
+        let endpoint_wait = self
+            .endpoints
+            .get(&endpoint)
+            .map_or(Duration::ZERO, |b| b.reserve(now));
+        let wait = global_wait.max(endpoint_wait);
+
+        {
+            let mut metrics = self.metrics.lock().unwrap();
+            metrics.total.record(wait);
+            metrics.endpoints.entry(endpoint).or_default().record(wait);
+        }
+
+        if !wait.is_zero() {
+            tokio::time::sleep_until(now + wait).await;
+        }
+        wait
+    }
+
+    /// Snapshot of the throttling counters
+    pub fn metrics(&self) -> ThrottleMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+}
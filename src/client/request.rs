@@ -3,41 +3,144 @@
 //! This module provides HTTP functionality for non-WASM environments
 //! using the reqwest crate for making HTTP requests.
 
-use crate::error::{ErrorKind, Result};
+use crate::client::config::ServerConfig;
+use crate::client::rate_limit::{Endpoint, RateLimiter};
+use crate::error::{Error, ErrorKind, Result};
+use cookie_store::{Cookie, CookieStore};
 use reqwest::{
-    Client,
+    Client, Response, StatusCode,
     header::{HeaderMap, HeaderValue},
 };
-use serde_json::Value;
+use reqwest_cookie_store::CookieStoreMutex;
+use serde::de::DeserializeOwned;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use crate::interface::{HttpClient, RequestApi};
 use crate::model::dtos::{CourseQueryParams, CourseSelectParams, LoginParams};
+use crate::model::responses::{
+    ApiResponse, CaptchaData, CaptchaResponse, CourseListResponse, LoginResponse,
+};
 
 /// HTTP client for no-WASM environments using reqwest
 #[derive(Debug, Clone)]
 pub struct NoWasmClient {
     client: Client,
+    config: Arc<ServerConfig>,
+    limiter: Option<Arc<RateLimiter>>,
+    /// Cookie jar of `client`, `None` for clients wrapped with `from_client`
+    cookies: Option<Arc<CookieStoreMutex>>,
 }
 
 impl HttpClient for NoWasmClient {
-    async fn new() -> Result<Self> {
+    async fn with_config(config: ServerConfig) -> Result<Self> {
+        let cookies = Arc::new(CookieStoreMutex::default());
         let client = Client::builder()
             .danger_accept_invalid_certs(true)
+            .cookie_provider(Arc::clone(&cookies))
             .build()?;
 
-        Ok(Self { client })
+        Ok(Self {
+            cookies: Some(cookies),
+            ..Self::from_client(client, config)
+        })
+    }
+
+    fn config(&self) -> &ServerConfig {
+        &self.config
+    }
+}
+
+impl NoWasmClient {
+    /// Wrap an existing reqwest client
+    pub fn from_client(client: Client, config: ServerConfig) -> Self {
+        Self {
+            client,
+            config: Arc::new(config),
+            limiter: None,
+            cookies: None,
+        }
+    }
+
+    /// Unexpired cookies held by the client, including session cookies
+    pub fn cookies(&self) -> Vec<Cookie<'static>> {
+        let Some(store) = &self.cookies else {
+            return Vec::new();
+        };
+        store.lock().unwrap().iter_unexpired().cloned().collect()
+    }
+
+    /// Replace the client's cookies, e.g. with ones saved by a previous run
+    pub fn restore_cookies(&self, cookies: Vec<Cookie<'static>>) -> Result<()> {
+        let Some(store) = &self.cookies else {
+            return Ok(());
+        };
+        let restored = CookieStore::from_cookies(cookies.into_iter().map(Ok::<_, Error>), false)
+            .map_err(|e| ErrorKind::ParseError(format!("Invalid cookies: {e}")))?;
+        *store.lock().unwrap() = restored;
+        Ok(())
+    }
+
+    /// Route every request through a shared rate limiter
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    pub fn rate_limiter(&self) -> Option<&Arc<RateLimiter>> {
+        self.limiter.as_ref()
+    }
+
+    /// Wait for the rate limiter before a request to `endpoint`
+    async fn throttle(&self, endpoint: Endpoint) {
+        if let Some(limiter) = &self.limiter {
+            limiter.acquire(endpoint).await;
+        }
+    }
+
+    /// Underlying reqwest client
+    pub fn inner(&self) -> &Client {
+        &self.client
+    }
+
+    fn url(&self, path: &str) -> String {
+        self.config.url(path)
+    }
+
+    /// Deserialize a JSON response body, keeping serde's error location
+    ///
+    /// Business errors may come with a non-2xx status, so the `{code, msg}`
+    /// envelope is tried first and [`ErrorKind::HttpStatus`] is only returned
+    /// for bodies that are not one.
+    async fn parse<T: DeserializeOwned>(resp: Response) -> Result<T> {
+        let status = resp.status();
+        if status == StatusCode::UNAUTHORIZED {
+            return Err(ErrorKind::TokenExpired.into());
+        }
+        let url = resp.url().to_string();
+
+        let text = resp.text().await?;
+        match serde_json::from_str(&text) {
+            Ok(body) => Ok(body),
+            Err(_) if !status.is_success() => Err(ErrorKind::HttpStatus {
+                status: status.as_u16(),
+                url,
+            }
+            .into()),
+            Err(e) => Err(e.into()),
+        }
     }
 }
 
 impl RequestApi for NoWasmClient {
     #[allow(clippy::sliced_string_as_bytes)]
     async fn get_aes_key(&self) -> Result<Vec<u8>> {
-        let index_url = "https://icourses.jlu.edu.cn/";
+        let index_url = self.url(&self.config.endpoints.index);
 
         // 添加重试机制
         for attempt in 1..=3 {
-            match self.client.get(index_url).send().await {
+            self.throttle(Endpoint::Index).await;
+            match self.client.get(&index_url).send().await {
                 Ok(resp) => {
                     let status = resp.status();
                     if !status.is_success() {
@@ -47,9 +150,11 @@ impl RequestApi for NoWasmClient {
                             status.canonical_reason().unwrap_or("Unknown")
                         );
                         if attempt == 3 {
-                            return Err(
-                                ErrorKind::ParseError(format!("HTTP error: {status}")).into()
-                            );
+                            return Err(ErrorKind::HttpStatus {
+                                status: status.as_u16(),
+                                url: index_url,
+                            }
+                            .into());
                         }
                         continue;
                     }
@@ -78,11 +183,9 @@ impl RequestApi for NoWasmClient {
                             })
                         })
                         .flatten()
-                        .ok_or_else(|| {
-                            ErrorKind::ParseError("Failed to extract AES key from HTML".to_string())
-                        })?;
+                        .ok_or(ErrorKind::AesKeyNotFound)?;
 
-                    println!("AES key extracted successfully");
+                    eprintln!("AES key extracted successfully");
                     return Ok(key);
                 }
                 Err(e) => {
@@ -98,26 +201,16 @@ impl RequestApi for NoWasmClient {
         unreachable!()
     }
 
-    async fn get_captcha(&self) -> Result<(String, String)> {
-        let captcha_url = "https://icourses.jlu.edu.cn/xsxk/auth/captcha";
+    async fn get_captcha(&self) -> Result<CaptchaData> {
+        let captcha_url = self.url(&self.config.endpoints.captcha);
+        self.throttle(Endpoint::Captcha).await;
         let resp = self.client.post(captcha_url).send().await?;
-        let captcha_data = resp.json::<Value>().await?;
 
-        let uuid = captcha_data["data"]["uuid"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha uuid".to_string()))?
-            .to_string();
-
-        let captcha = captcha_data["data"]["captcha"]
-            .as_str()
-            .ok_or_else(|| ErrorKind::ParseError("Invalid captcha data".to_string()))?
-            .to_string();
-
-        Ok((uuid, captcha))
+        Self::parse::<CaptchaResponse>(resp).await?.into_data()
     }
 
-    async fn send_login_request(&self, params: LoginParams) -> Result<Value> {
-        let login_url = "https://icourses.jlu.edu.cn/xsxk/auth/login";
+    async fn send_login_request(&self, params: LoginParams) -> Result<LoginResponse> {
+        let login_url = self.url(&self.config.endpoints.login);
 
         let mut query_params = HashMap::new();
         query_params.insert("loginname", params.username);
@@ -125,6 +218,7 @@ impl RequestApi for NoWasmClient {
         query_params.insert("captcha", params.captcha);
         query_params.insert("uuid", params.uuid);
 
+        self.throttle(Endpoint::Login).await;
         let resp = self
             .client
             .post(login_url)
@@ -132,11 +226,11 @@ impl RequestApi for NoWasmClient {
             .send()
             .await?;
 
-        Ok(resp.json::<Value>().await?)
+        Self::parse(resp).await
     }
 
-    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/elective/user";
+    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<ApiResponse> {
+        let url = self.url(&self.config.endpoints.elective_user);
         let mut params = HashMap::new();
         params.insert("batchId", batch_id);
 
@@ -146,6 +240,7 @@ impl RequestApi for NoWasmClient {
             HeaderValue::from_str(token).map_err(|e| ErrorKind::ParseError(e.to_string()))?,
         );
 
+        self.throttle(Endpoint::ElectiveUser).await;
         let resp = self
             .client
             .post(url)
@@ -154,8 +249,8 @@ impl RequestApi for NoWasmClient {
             .send()
             .await?;
 
-        let get_url =
-            format!("https://icourses.jlu.edu.cn/xsxk/elective/grablessons?batchId={batch_id}");
+        let get_url = self.config.grablessons_url(batch_id);
+        self.throttle(Endpoint::Grablessons).await;
         self.client
             .get(&get_url)
             .header("Authorization", token)
@@ -163,11 +258,11 @@ impl RequestApi for NoWasmClient {
             .send()
             .await?;
 
-        Ok(resp.json::<Value>().await?)
+        Self::parse(resp).await
     }
 
-    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/elective/select";
+    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse> {
+        let url = self.url(&self.config.endpoints.select);
         let mut headers = HeaderMap::new();
         headers.insert(
             "Authorization",
@@ -180,13 +275,14 @@ impl RequestApi for NoWasmClient {
                 .map_err(|e| ErrorKind::ParseError(e.to_string()))?,
         );
 
+        self.throttle(Endpoint::Select).await;
         let resp = self.client.post(url).headers(headers).send().await?;
 
-        Ok(resp.json::<Value>().await?)
+        Self::parse(resp).await
     }
 
-    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/sc/clazz/list";
+    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse> {
+        let url = self.url(&self.config.endpoints.favorite_list);
         let mut headers = HeaderMap::new();
         headers.insert(
             "Authorization",
@@ -200,20 +296,18 @@ impl RequestApi for NoWasmClient {
         );
         headers.insert(
             "Referer",
-            HeaderValue::from_str(&format!(
-                "https://icourses.jlu.edu.cn/xsxk/elective/grablessons?batchId={}",
-                params.batch_id
-            ))
-            .map_err(|e| ErrorKind::ParseError(e.to_string()))?,
+            HeaderValue::from_str(&self.config.grablessons_url(&params.batch_id))
+                .map_err(|e| ErrorKind::ParseError(e.to_string()))?,
         );
 
+        self.throttle(Endpoint::FavoriteList).await;
         let resp = self.client.post(url).headers(headers).send().await?;
 
-        Ok(resp.json::<Value>().await?)
+        Self::parse(resp).await
     }
 
-    async fn select_course(&self, params: CourseSelectParams) -> Result<Value> {
-        let url = "https://icourses.jlu.edu.cn/xsxk/sc/clazz/addxk";
+    async fn select_course(&self, params: CourseSelectParams) -> Result<ApiResponse> {
+        let url = self.url(&self.config.endpoints.add_course);
         let mut headers = HeaderMap::new();
         headers.insert(
             "Authorization",
@@ -231,6 +325,7 @@ impl RequestApi for NoWasmClient {
         query_params.insert("clazzId", params.class_id);
         query_params.insert("secretVal", params.secret_val);
 
+        self.throttle(Endpoint::AddCourse).await;
         let resp = self
             .client
             .post(url)
@@ -239,7 +334,7 @@ impl RequestApi for NoWasmClient {
             .send()
             .await?;
 
-        Ok(resp.json::<Value>().await?)
+        Self::parse(resp).await
     }
 }
 
@@ -252,16 +347,12 @@ pub async fn create_client() -> Result<Client> {
 }
 
 pub async fn get_aes_key(client: &Client) -> Result<Vec<u8>> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     wrapper.get_aes_key().await
 }
 
-pub async fn get_captcha(client: &Client) -> Result<(String, String)> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+pub async fn get_captcha(client: &Client) -> Result<CaptchaData> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     wrapper.get_captcha().await
 }
 
@@ -271,10 +362,8 @@ pub async fn send_login_request(
     encrypted_password: &str,
     captcha: &str,
     uuid: &str,
-) -> Result<Value> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+) -> Result<LoginResponse> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     let params = LoginParams {
         username: username.to_string(),
         encrypted_password: encrypted_password.to_string(),
@@ -284,17 +373,17 @@ pub async fn send_login_request(
     wrapper.send_login_request(params).await
 }
 
-pub async fn set_batch(client: &Client, batch_id: &str, token: &str) -> Result<Value> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+pub async fn set_batch(client: &Client, batch_id: &str, token: &str) -> Result<ApiResponse> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     wrapper.set_batch(batch_id, token).await
 }
 
-pub async fn get_selected_courses(client: &Client, token: &str, batch_id: &str) -> Result<Value> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+pub async fn get_selected_courses(
+    client: &Client,
+    token: &str,
+    batch_id: &str,
+) -> Result<CourseListResponse> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -302,10 +391,12 @@ pub async fn get_selected_courses(client: &Client, token: &str, batch_id: &str)
     wrapper.get_selected_courses(params).await
 }
 
-pub async fn get_favorite_courses(client: &Client, token: &str, batch_id: &str) -> Result<Value> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+pub async fn get_favorite_courses(
+    client: &Client,
+    token: &str,
+    batch_id: &str,
+) -> Result<CourseListResponse> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     let params = CourseQueryParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
@@ -320,10 +411,8 @@ pub async fn select_course(
     class_type: &str,
     class_id: &str,
     secret_val: &str,
-) -> Result<Value> {
-    let wrapper = NoWasmClient {
-        client: client.clone(),
-    };
+) -> Result<ApiResponse> {
+    let wrapper = NoWasmClient::from_client(client.clone(), ServerConfig::default());
     let params = CourseSelectParams {
         token: token.to_string(),
         batch_id: batch_id.to_string(),
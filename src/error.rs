@@ -1,3 +1,5 @@
+use crate::model::structs::{BatchInfo, BatchSelector};
+
 pub type Result<T> = core::result::Result<T, Error>;
 
 pub struct Error {
@@ -10,6 +12,29 @@ impl Error {
             inner: Box::new(kind),
         }
     }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.inner
+    }
+
+    /// The session token is no longer accepted and a fresh login is required
+    pub fn is_token_expired(&self) -> bool {
+        matches!(*self.inner, ErrorKind::TokenExpired)
+    }
+
+    /// A network failure or server-side error that may go away on retry
+    pub fn is_transient(&self) -> bool {
+        match &*self.inner {
+            #[cfg(feature = "no-wasm")]
+            ErrorKind::ReqwestError(e) => {
+                e.is_timeout() || e.is_connect() || e.is_request() || e.is_body()
+            }
+            #[cfg(feature = "wasm")]
+            ErrorKind::GlooNetError(_) => true,
+            ErrorKind::HttpStatus { status, .. } => *status >= 500 || *status == 429,
+            _ => false,
+        }
+    }
 }
 
 impl std::fmt::Debug for Error {
@@ -24,6 +49,21 @@ impl std::fmt::Display for Error {
     }
 }
 
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match *self.inner {
+            #[cfg(feature = "no-wasm")]
+            ErrorKind::ReqwestError(ref e) => Some(e),
+            #[cfg(feature = "wasm")]
+            ErrorKind::GlooNetError(ref e) => Some(e),
+            ErrorKind::SerdeJsonError(ref e) => Some(e),
+            ErrorKind::Base64Error(ref e) => Some(e),
+            ErrorKind::StdIoError(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 impl From<ErrorKind> for Error {
     fn from(kind: ErrorKind) -> Error {
         Error::new(kind)
@@ -72,6 +112,53 @@ pub enum ErrorKind {
     StdIoError(std::io::Error),
     ParseError(String),
     CourseError(String),
+    /// Login refused by the server, e.g. wrong username or password
+    LoginRejected {
+        msg: String,
+    },
+    /// Captcha answer was not accepted
+    CaptchaWrong,
+    /// Captcha solver gave no answer, e.g. timed out or its UI went away
+    CaptchaUnsolved(String),
+    /// Token rejected with 401, a fresh login is required
+    TokenExpired,
+    /// No entry of `electiveBatchList` matches `selector`
+    BatchNotFound {
+        selector: BatchSelector,
+        available: Vec<BatchInfo>,
+    },
+    /// `selector` matches several batches
+    BatchAmbiguous {
+        selector: BatchSelector,
+        matches: Vec<BatchInfo>,
+    },
+    /// The batch closed at `end_time` before enrollment could start
+    BatchEnded {
+        end_time: String,
+    },
+    /// Non-success HTTP status
+    HttpStatus {
+        status: u16,
+        url: String,
+    },
+    /// `loginVue.loginForm.aesKey` missing from the index page
+    AesKeyNotFound,
+    /// Credential vault could not be decrypted, usually a wrong passphrase
+    VaultLocked,
+}
+
+fn write_batches(f: &mut std::fmt::Formatter, batches: &[BatchInfo]) -> std::fmt::Result {
+    if batches.is_empty() {
+        return write!(f, " (none)");
+    }
+    for batch in batches {
+        write!(
+            f,
+            "\n  {} {} ({} ~ {})",
+            batch.code, batch.name, batch.begin_time, batch.end_time
+        )?;
+    }
+    Ok(())
 }
 
 impl std::fmt::Debug for ErrorKind {
@@ -86,6 +173,30 @@ impl std::fmt::Debug for ErrorKind {
             ErrorKind::StdIoError(ref e) => write!(f, "StdIoError: {e:?}"),
             ErrorKind::ParseError(ref e) => write!(f, "ParseError: {e:?}"),
             ErrorKind::CourseError(ref e) => write!(f, "CourseError: {e:?}"),
+            ErrorKind::LoginRejected { ref msg } => write!(f, "LoginRejected: {msg:?}"),
+            ErrorKind::CaptchaWrong => write!(f, "CaptchaWrong"),
+            ErrorKind::CaptchaUnsolved(ref e) => write!(f, "CaptchaUnsolved: {e:?}"),
+            ErrorKind::TokenExpired => write!(f, "TokenExpired"),
+            ErrorKind::BatchNotFound {
+                ref selector,
+                ref available,
+            } => {
+                write!(f, "BatchNotFound: no batch matches {selector}, available:")?;
+                write_batches(f, available)
+            }
+            ErrorKind::BatchAmbiguous {
+                ref selector,
+                ref matches,
+            } => {
+                write!(f, "BatchAmbiguous: {selector} matches several batches:")?;
+                write_batches(f, matches)
+            }
+            ErrorKind::BatchEnded { ref end_time } => write!(f, "BatchEnded: closed at {end_time}"),
+            ErrorKind::HttpStatus { status, ref url } => {
+                write!(f, "HttpStatus: {status} from {url}")
+            }
+            ErrorKind::AesKeyNotFound => write!(f, "AesKeyNotFound"),
+            ErrorKind::VaultLocked => write!(f, "VaultLocked: wrong passphrase or corrupted vault"),
         }
     }
 }
@@ -102,6 +213,30 @@ impl std::fmt::Display for ErrorKind {
             ErrorKind::StdIoError(ref e) => write!(f, "StdIoError: {e:?}"),
             ErrorKind::ParseError(ref e) => write!(f, "ParseError: {e:?}"),
             ErrorKind::CourseError(ref e) => write!(f, "CourseError: {e:?}"),
+            ErrorKind::LoginRejected { ref msg } => write!(f, "LoginRejected: {msg:?}"),
+            ErrorKind::CaptchaWrong => write!(f, "CaptchaWrong"),
+            ErrorKind::CaptchaUnsolved(ref e) => write!(f, "CaptchaUnsolved: {e:?}"),
+            ErrorKind::TokenExpired => write!(f, "TokenExpired"),
+            ErrorKind::BatchNotFound {
+                ref selector,
+                ref available,
+            } => {
+                write!(f, "BatchNotFound: no batch matches {selector}, available:")?;
+                write_batches(f, available)
+            }
+            ErrorKind::BatchAmbiguous {
+                ref selector,
+                ref matches,
+            } => {
+                write!(f, "BatchAmbiguous: {selector} matches several batches:")?;
+                write_batches(f, matches)
+            }
+            ErrorKind::BatchEnded { ref end_time } => write!(f, "BatchEnded: closed at {end_time}"),
+            ErrorKind::HttpStatus { status, ref url } => {
+                write!(f, "HttpStatus: {status} from {url}")
+            }
+            ErrorKind::AesKeyNotFound => write!(f, "AesKeyNotFound"),
+            ErrorKind::VaultLocked => write!(f, "VaultLocked: wrong passphrase or corrupted vault"),
         }
     }
 }
@@ -1,15 +1,27 @@
 #![allow(async_fn_in_trait)] // 允许在内部 trait 中使用 async fn
 
+use crate::client::config::ServerConfig;
 use crate::error::Result;
 use crate::model::dtos::{CourseQueryParams, CourseSelectParams, LoginParams};
-use serde_json::Value;
+use crate::model::responses::{ApiResponse, CaptchaData, CourseListResponse, LoginResponse};
 
 /// Common trait for HTTP client functionality
 pub trait HttpClient {
-    /// Create a new HTTP client instance
+    /// Create a new HTTP client instance against the default server
     async fn new() -> Result<Self>
+    where
+        Self: Sized,
+    {
+        Self::with_config(ServerConfig::default()).await
+    }
+
+    /// Create a new HTTP client instance against the given server
+    async fn with_config(config: ServerConfig) -> Result<Self>
     where
         Self: Sized;
+
+    /// Server configuration this client was built with
+    fn config(&self) -> &ServerConfig;
 }
 
 /// Common interface for all HTTP operations
@@ -18,20 +30,20 @@ pub trait RequestApi {
     async fn get_aes_key(&self) -> Result<Vec<u8>>;
 
     /// Get captcha image and UUID
-    async fn get_captcha(&self) -> Result<(String, String)>;
+    async fn get_captcha(&self) -> Result<CaptchaData>;
 
     /// Send login request with credentials
-    async fn send_login_request(&self, params: LoginParams) -> Result<Value>;
+    async fn send_login_request(&self, params: LoginParams) -> Result<LoginResponse>;
 
     /// Set the current batch for course selection
-    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<Value>;
+    async fn set_batch(&self, batch_id: &str, token: &str) -> Result<ApiResponse>;
 
     /// Get list of selected courses
-    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<Value>;
+    async fn get_selected_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse>;
 
     /// Get list of favorite courses
-    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<Value>;
+    async fn get_favorite_courses(&self, params: CourseQueryParams) -> Result<CourseListResponse>;
 
     /// Select a course
-    async fn select_course(&self, params: CourseSelectParams) -> Result<Value>;
+    async fn select_course(&self, params: CourseSelectParams) -> Result<ApiResponse>;
 }
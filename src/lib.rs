@@ -4,6 +4,8 @@ pub mod crypto;
 pub mod error;
 pub mod interface;
 pub mod model;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 #[cfg(feature = "no-wasm")]
 pub use reqwest::Client;
@@ -11,3 +13,5 @@ pub use reqwest::Client;
 pub use tokio;
 #[cfg(feature = "no-wasm")]
 pub use tokio::sync::Mutex as TokioMutex;
+#[cfg(feature = "no-wasm")]
+pub use tokio_util::sync::CancellationToken;
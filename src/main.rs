@@ -1,81 +1,1011 @@
-use funky_lesson_core::app::{enroll_courses, get_courses, login, print_courses, set_batch};
-use funky_lesson_core::client::request::create_client;
+use chrono::Utc;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use funky_lesson_core::app::captcha::StdinSolver;
+use funky_lesson_core::app::config::{AppConfig, PasswordSource};
+use funky_lesson_core::app::engine::{EnrollEvent, EnrollReport, EnrollmentEngine};
+use funky_lesson_core::app::pool::{AccountPool, PoolEvent};
+use funky_lesson_core::app::scheduler::{Scheduler, shanghai};
+use funky_lesson_core::app::session::{Credentials, Session, SessionManager};
+use funky_lesson_core::app::store::{SavedSession, SessionStore};
+use funky_lesson_core::app::vault::CredentialVault;
+use funky_lesson_core::app::{
+    enroll_courses_scheduled, enroll_courses_with_session, find_batch, print_courses,
+    print_preflight, print_session_events, print_summary,
+};
+use funky_lesson_core::client::clock::ClockSync;
+use funky_lesson_core::client::rate_limit::RateLimiter;
+use funky_lesson_core::client::request::NoWasmClient;
 use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::interface::HttpClient;
+use funky_lesson_core::model::structs::{
+    BatchSelector, CourseInfo, CourseOutcome, CourseTarget, EnrollOutcome,
+};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_util::sync::CancellationToken;
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
+#[derive(Parser)]
+#[command(version, about = "吉林大学抢课工具")]
+struct Cli {
+    #[command(flatten)]
+    global: GlobalArgs,
+    #[command(subcommand)]
+    command: Command,
+}
 
-    if args.len() < 4 {
-        println!(
-            "用法: {} 用户名 密码 选课批次ID（从0开始） <有循环就填个数>",
-            args[0]
-        );
-        return Ok(());
+#[derive(Args)]
+struct GlobalArgs {
+    /// 从 TOML/JSON 配置文件读取设置，命令行参数优先
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// 用户名
+    #[arg(long, global = true)]
+    username: Option<String>,
+    /// 从环境变量读取密码
+    #[arg(long, global = true, value_name = "VAR")]
+    password_env: Option<String>,
+    /// 从文件第一行读取密码
+    #[arg(long, global = true, value_name = "PATH")]
+    password_file: Option<PathBuf>,
+    /// 加密保存密码的保险库文件 (默认 funky_vault.json)
+    #[arg(long, global = true, value_name = "PATH")]
+    vault: Option<PathBuf>,
+    /// 选课批次: 序号（从0开始）、批次代码、name:名称的一部分或 auto (默认 auto)
+    #[arg(long, global = true)]
+    batch: Option<BatchSelector>,
+    /// 保存登录状态的文件
+    #[arg(long, global = true, default_value = "funky_session.json")]
+    session_file: PathBuf,
+    /// 所有接口合计每秒最多请求数，0 为不限 (默认 10)
+    #[arg(long, global = true)]
+    global_rps: Option<f64>,
+    /// 全局限流允许的突发请求数 (默认 4)
+    #[arg(long, global = true)]
+    global_burst: Option<u32>,
+    /// 选课接口每秒最多请求数，所有线程和账号合计 (默认不限)
+    #[arg(long, global = true, visible_alias = "max-rps")]
+    addxk_rps: Option<f64>,
+    /// 以 JSON 格式输出结果
+    #[arg(long, global = true)]
+    json: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// 登录并保存会话
+    Login,
+    /// 列出选课批次及其时间
+    Batches,
+    /// 列出已选或收藏的课程
+    Courses {
+        #[arg(value_enum)]
+        list: CourseList,
+    },
+    /// 选课一轮，配置了 loop 时循环
+    Enroll(EnrollArgs),
+    /// 循环选课，直到目标课程全部选上
+    Watch(EnrollArgs),
+    /// 查看保存的会话是否有效及已选课程
+    Status,
+    /// 管理加密保存的账号密码
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction,
+    },
+}
+
+#[derive(Subcommand)]
+enum VaultAction {
+    /// 保存或更新账号密码，密码可用 --password-env/--password-file 提供，否则提示输入
+    Add { username: String },
+    /// 删除账号
+    Remove { username: String },
+    /// 列出保存的账号，无需口令
+    List,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CourseList {
+    Selected,
+    Favorites,
+}
+
+#[derive(Args)]
+struct EnrollArgs {
+    /// 只选择匹配的收藏课程: 教学班ID、课程名或教师，也可写 id:/name:/teacher: 前缀，可重复
+    #[arg(long = "course", value_name = "COURSE")]
+    courses: Vec<CourseTarget>,
+    /// 一组互斥的备选课程，逗号分隔并按优先级排列，选上一门后不再尝试其余课程，可重复
+    #[arg(long = "group", value_name = "COURSES")]
+    groups: Vec<String>,
+    /// 并发工作线程数 (默认 4)
+    #[arg(long)]
+    workers: Option<usize>,
+    /// 每个线程两次请求之间的间隔 (默认 0)
+    #[arg(long)]
+    interval_ms: Option<u64>,
+    /// 在间隔上随机增加 0..=N 毫秒 (默认 0)
+    #[arg(long)]
+    jitter_ms: Option<u64>,
+    /// 出错后的初始退避时间 (默认 500)
+    #[arg(long)]
+    backoff_ms: Option<u64>,
+    /// 退避时间上限 (默认 8000)
+    #[arg(long)]
+    backoff_max_ms: Option<u64>,
+    /// 两轮之间的间隔 (默认 500)
+    #[arg(long)]
+    round_interval_ms: Option<u64>,
+    /// 等到批次开始时间再选课，结束时间后自动停止
+    #[arg(long)]
+    schedule: bool,
+    /// 提前于开始时间多少毫秒开始选课 (默认 1000)
+    #[arg(long)]
+    lead_ms: Option<u64>,
+    /// 开始前多少秒刷新登录状态 (默认 30)
+    #[arg(long)]
+    warm_up_secs: Option<u64>,
+    /// 用于校准服务器时钟的请求数，0 为不校准 (默认 5)
+    #[arg(long)]
+    clock_samples: Option<usize>,
+}
+
+impl GlobalArgs {
+    /// Load `--config` if given and override it with the global flags
+    fn app_config(&self) -> Result<AppConfig> {
+        let mut config = match &self.config {
+            Some(path) => AppConfig::load(path)?,
+            None => AppConfig::default(),
+        };
+        if let Some(username) = &self.username {
+            config.username = Some(username.clone());
+        }
+        if let Some(env) = &self.password_env {
+            config.password = Some(PasswordSource::Env { env: env.clone() });
+        }
+        if let Some(file) = &self.password_file {
+            config.password = Some(PasswordSource::File { file: file.clone() });
+        }
+        if let Some(vault) = &self.vault {
+            config.vault = Some(vault.clone());
+        }
+        if let Some(batch) = &self.batch {
+            config.batch = Some(batch.clone());
+        }
+        let rate_limit = &mut config.rate_limit;
+        rate_limit.global_rps = self.global_rps.or(rate_limit.global_rps);
+        rate_limit.global_burst = self.global_burst.or(rate_limit.global_burst);
+        rate_limit.addxk_rps = self.addxk_rps.or(rate_limit.addxk_rps);
+        Ok(config)
     }
+}
 
-    println!("args: {args:?}");
+impl EnrollArgs {
+    fn apply(&self, config: &mut AppConfig) {
+        if !self.courses.is_empty() {
+            config.courses = self.courses.clone();
+        }
+        if !self.groups.is_empty() {
+            config.groups = self
+                .groups
+                .iter()
+                .map(|group| {
+                    group
+                        .split(',')
+                        .map(|target| {
+                            let Ok(target) = target.parse();
+                            target
+                        })
+                        .collect()
+                })
+                .collect();
+        }
+        let enroll = &mut config.enroll;
+        enroll.workers = self.workers.or(enroll.workers);
+        enroll.interval_ms = self.interval_ms.or(enroll.interval_ms);
+        enroll.jitter_ms = self.jitter_ms.or(enroll.jitter_ms);
+        enroll.backoff_ms = self.backoff_ms.or(enroll.backoff_ms);
+        enroll.backoff_max_ms = self.backoff_max_ms.or(enroll.backoff_max_ms);
+        enroll.round_interval_ms = self.round_interval_ms.or(enroll.round_interval_ms);
 
-    let username = args[1].clone();
-    let password = args[2].clone();
-    let batch_idx: usize = args[3]
-        .parse()
-        .map_err(|e| ErrorKind::ParseError(format!("Invalid batch index: {e}")))?;
-    let mut debug_request_count = 0;
+        let schedule = &mut config.schedule;
+        if self.schedule {
+            schedule.enabled = Some(true);
+        }
+        schedule.lead_ms = self.lead_ms.or(schedule.lead_ms);
+        schedule.warm_up_secs = self.warm_up_secs.or(schedule.warm_up_secs);
+        schedule.clock_samples = self.clock_samples.or(schedule.clock_samples);
+    }
+}
 
-    loop {
-        println!("Creating client...");
-        let client = match create_client().await {
-            Ok(client) => {
-                println!("Client created successfully");
-                client
-            }
+/// Everything a subcommand needs
+struct Context {
+    config: AppConfig,
+    store: SessionStore,
+    json: bool,
+    limiter: Arc<RateLimiter>,
+    session: Arc<SessionManager>,
+}
+
+impl Context {
+    async fn new(global: &GlobalArgs, config: AppConfig) -> Result<Self> {
+        let limiter = Arc::new(config.rate_limiter());
+        let server = config.server.clone().unwrap_or_default();
+        let client = NoWasmClient::with_config(server)
+            .await?
+            .with_rate_limiter(Arc::clone(&limiter));
+
+        let store = SessionStore::new(&global.session_file);
+        // 会话文件损坏或格式过旧时重新登录，login 命令正是用来修复它的
+        let saved = match store.load() {
+            Ok(saved) => saved.filter(|saved| saved_matches(&config, saved)),
             Err(e) => {
-                eprintln!("Failed to create client: {e}");
-                return Err(e);
+                eprintln!("无法读取保存的会话，将重新登录: {e}");
+                None
             }
         };
-
-        let (token, batch_list) = loop {
-            println!("Attempting login...");
-            match login(&client, &username, &password).await {
-                Ok(result) => {
-                    println!("Login successful");
-                    break result;
+        let password = match &config.username {
+            Some(username) => {
+                VaultPasswords::new(&config).password(config.password.as_ref(), username)?
+            }
+            None => None,
+        };
+        let session = match (&config.username, password) {
+            (Some(username), Some(password)) => {
+                let credentials = Credentials {
+                    username: username.clone(),
+                    password,
+                };
+                let batch = config.batch.clone().unwrap_or(BatchSelector::Auto);
+                SessionManager::new(client, credentials, batch, Arc::new(StdinSolver::default()))
+            }
+            // 没有账号密码时只能使用保存的会话
+            _ => match &saved {
+                Some(saved) => SessionManager::with_token(
+                    client,
+                    &saved.session.token,
+                    &saved.session.batch_id,
+                ),
+                None => {
+                    return Err(ErrorKind::ParseError(
+                        "需要 --username 和密码，或先运行 login 保存会话".to_string(),
+                    )
+                    .into());
                 }
-                Err(e) => {
-                    eprintln!("登录失败: {e}，重试中...");
-                    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+            },
+        };
+
+        tokio::spawn(print_session_events(session.subscribe(), None));
+        if let Some(saved) = saved {
+            match session.resume(saved).await {
+                Ok(true) => eprintln!("已恢复保存的会话"),
+                Ok(false) => eprintln!("保存的会话已失效，需要重新登录"),
+                Err(e) => eprintln!("无法验证保存的会话，将重新登录: {e}"),
+            }
+        }
+
+        Ok(Self {
+            config,
+            store,
+            json: global.json,
+            limiter,
+            session: Arc::new(session),
+        })
+    }
+
+    /// Log in if needed, retrying transient failures
+    async fn current(&self) -> Result<Session> {
+        let backoff = self.config.enroll_config().backoff;
+        let mut failures = 0;
+        loop {
+            match self.session.current().await {
+                Ok(session) => return Ok(session),
+                Err(e) => match e.kind() {
+                    // 用户名或密码错误，重试没有意义
+                    ErrorKind::LoginRejected { msg } => {
+                        eprintln!("登录被拒绝: {msg}");
+                        return Err(e);
+                    }
+                    // 只有网络故障和服务器错误可能自行恢复，其余错误重试也不会成功
+                    _ if e.is_transient() => {
+                        eprintln!("登录失败: {e}，重试中...");
+                        failures += 1;
+                        tokio::time::sleep(backoff.delay(failures)).await;
+                    }
+                    _ => return Err(e),
+                },
+            }
+        }
+    }
+
+    /// Write the current session back, it may have been refreshed
+    async fn save(&self) -> Result<()> {
+        match self.session.snapshot().await {
+            Some(saved) => self.store.save(&saved),
+            None => Ok(()),
+        }
+    }
+
+    /// Print `value` as JSON, or run `human` otherwise
+    fn output<T: Serialize>(&self, value: &T, human: impl FnOnce()) -> Result<()> {
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(value)?);
+        } else {
+            human();
+        }
+        Ok(())
+    }
+}
+
+const VAULT_PASSPHRASE_ENV: &str = "FUNKY_VAULT_PASSPHRASE";
+
+fn vault_path(config: &AppConfig) -> PathBuf {
+    config
+        .vault
+        .clone()
+        .unwrap_or_else(|| PathBuf::from("funky_vault.json"))
+}
+
+/// Passphrase from `FUNKY_VAULT_PASSPHRASE`, else asked on the terminal
+fn vault_passphrase(confirm: bool) -> Result<String> {
+    if let Ok(passphrase) = std::env::var(VAULT_PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase = rpassword::prompt_password("保险库口令: ")?;
+    if confirm && rpassword::prompt_password("再次输入口令: ")? != passphrase {
+        return Err(ErrorKind::ParseError("两次输入的口令不一致".to_string()).into());
+    }
+    Ok(passphrase)
+}
+
+/// Vault passwords, unlocked the first time an account in it is needed
+struct VaultPasswords {
+    path: PathBuf,
+    vault: Option<CredentialVault>,
+}
+
+impl VaultPasswords {
+    fn new(config: &AppConfig) -> Self {
+        Self {
+            path: vault_path(config),
+            vault: None,
+        }
+    }
+
+    /// `source` if given, else the password saved in the vault for `username`
+    fn password(
+        &mut self,
+        source: Option<&PasswordSource>,
+        username: &str,
+    ) -> Result<Option<String>> {
+        if let Some(source) = source {
+            return source.resolve().map(Some);
+        }
+        let vault = match &mut self.vault {
+            Some(vault) => vault,
+            None => {
+                if !CredentialVault::list(&self.path)?
+                    .iter()
+                    .any(|u| u == username)
+                {
+                    return Ok(None);
                 }
+                let vault = CredentialVault::open(&self.path, &vault_passphrase(false)?)?;
+                self.vault.insert(vault)
             }
         };
+        vault.get(username)
+    }
+}
+
+fn vault(config: &AppConfig, action: VaultAction, json: bool) -> Result<()> {
+    let path = vault_path(config);
+    match action {
+        VaultAction::Add { username } => {
+            let password = match &config.password {
+                Some(password) => password.resolve()?,
+                None => rpassword::prompt_password(format!("{username} 的密码: "))?,
+            };
+            // 新建保险库时确认口令，避免输错后再也打不开
+            let passphrase = vault_passphrase(!path.exists())?;
+            let mut vault = CredentialVault::open(&path, &passphrase)?;
+            let replaced = vault.insert(&username, &password)?;
+            vault.save()?;
+            if replaced {
+                eprintln!("已更新 {username} 的密码");
+            } else {
+                eprintln!("已保存 {username} 到 {}", path.display());
+            }
+        }
+        VaultAction::Remove { username } => {
+            let mut vault = CredentialVault::open(&path, &vault_passphrase(false)?)?;
+            if !vault.remove(&username) {
+                return Err(ErrorKind::ParseError(format!("保险库中没有 {username}")).into());
+            }
+            vault.save()?;
+            eprintln!("已删除 {username}");
+        }
+        VaultAction::List => {
+            let usernames = CredentialVault::list(&path)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&usernames)?);
+            } else if usernames.is_empty() {
+                println!("保险库中没有账号");
+            } else {
+                for username in usernames {
+                    println!("{username}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
 
-        // 设置批次
-        let batch_id = set_batch(&client, &token, &batch_list, batch_idx).await?;
+/// A saved session is only reused for the same account and batch
+fn saved_matches(config: &AppConfig, saved: &SavedSession) -> bool {
+    if let (Some(username), Some(saved_username)) = (&config.username, &saved.username)
+        && username != saved_username
+    {
+        return false;
+    }
+    let Some(selector) = &config.batch else {
+        return true;
+    };
+    let now = Utc::now().with_timezone(&shanghai());
+    find_batch(&saved.session.batch_list, selector, now)
+        .is_ok_and(|idx| saved.session.batch_list[idx].code == saved.session.batch_id)
+}
 
-        // 获取课程列表
-        let (selected_courses, favorite_courses) = get_courses(&client, &token, &batch_id).await?;
+#[derive(Serialize)]
+struct LoginSummary<'a> {
+    batch_id: &'a str,
+    batch_name: Option<&'a str>,
+    session_file: &'a Path,
+}
 
-        // 打印课程信息
-        print_courses(&selected_courses, &favorite_courses);
+#[derive(Serialize)]
+struct StatusSummary<'a> {
+    session_file: &'a Path,
+    username: Option<&'a str>,
+    batch_id: &'a str,
+    batch_name: Option<&'a str>,
+    valid: bool,
+    selected: Vec<CourseInfo>,
+}
+
+async fn login(ctx: &Context) -> Result<()> {
+    let session = ctx.current().await?;
+    ctx.save().await?;
 
-        // 开始选课
-        enroll_courses(&client, &token, &batch_id, &favorite_courses, true).await?;
+    let summary = LoginSummary {
+        batch_id: &session.batch_id,
+        batch_name: session.batch().map(|b| b.name.as_str()),
+        session_file: ctx.store.path(),
+    };
+    ctx.output(&summary, || {
+        println!("Login successful");
+        println!("会话已保存到 {}", ctx.store.path().display());
+    })
+}
 
-        // 更新并打印已选课程
-        let (selected_courses, _) = get_courses(&client, &token, &batch_id).await?;
-        print_courses(&selected_courses, &[]);
+async fn batches(ctx: &Context) -> Result<()> {
+    // 没有批次可选时仍然列出全部批次，以便用 --batch 选择
+    let (batch_list, batch_id) = match ctx.current().await {
+        Ok(session) => {
+            ctx.save().await?;
+            (session.batch_list, Some(session.batch_id))
+        }
+        Err(e) => match e.kind() {
+            ErrorKind::BatchNotFound {
+                selector,
+                available,
+            } => {
+                eprintln!("没有与 {selector} 匹配的批次，请用 --batch 选择");
+                (available.clone(), None)
+            }
+            _ => return Err(e),
+        },
+    };
 
-        debug_request_count += 1;
-        println!("DEBUG_REQUEST_COUNT: {debug_request_count}\n");
+    ctx.output(&batch_list, || {
+        for (idx, batch) in batch_list.iter().enumerate() {
+            let mark = if batch_id.as_ref() == Some(&batch.code) {
+                "*"
+            } else {
+                " "
+            };
+            println!(
+                "{mark}{idx:<3}{:<24}{:<40}{} ~ {}",
+                batch.code, batch.name, batch.begin_time, batch.end_time
+            );
+        }
+    })
+}
+
+async fn courses(ctx: &Context, list: CourseList) -> Result<()> {
+    ctx.current().await?;
+    let (selected, favorites) = ctx.session.get_courses().await?;
+    ctx.save().await?;
+
+    match list {
+        CourseList::Selected => ctx.output(&selected, || print_courses(&selected, &[])),
+        CourseList::Favorites => ctx.output(&favorites, || print_courses(&[], &favorites)),
+    }
+}
 
-        // 如果不是循环模式则退出
-        if args.len() == 4 {
+async fn status(ctx: &Context) -> Result<()> {
+    let saved = ctx
+        .store
+        .load()
+        .map_err(|e| ErrorKind::ParseError(format!("无法读取保存的会话，请重新运行 login: {e}")))?;
+    let Some(saved) = saved else {
+        return Err(ErrorKind::ParseError("没有保存的会话，请先运行 login".to_string()).into());
+    };
+    // 只检查保存的会话，过期时不重新登录
+    let valid = ctx.session.validate().await?;
+    let selected = if valid {
+        ctx.session.get_courses().await?.0
+    } else {
+        Vec::new()
+    };
+
+    let session = &saved.session;
+    let summary = StatusSummary {
+        session_file: ctx.store.path(),
+        username: saved.username.as_deref(),
+        batch_id: &session.batch_id,
+        batch_name: session.batch().map(|b| b.name.as_str()),
+        valid,
+        selected,
+    };
+    ctx.output(&summary, || {
+        if let Some(username) = summary.username {
+            println!("用户: {username}");
+        }
+        match session.batch() {
+            Some(batch) => println!(
+                "批次: {} {} ({} ~ {})",
+                batch.code, batch.name, batch.begin_time, batch.end_time
+            ),
+            None => println!("批次: {}", summary.batch_id),
+        }
+        if summary.valid {
+            println!("会话有效");
+            print_courses(&summary.selected, &[]);
+        } else {
+            println!("会话已过期，请重新运行 login");
+        }
+    })
+}
+
+/// Enroll round after round; `until_done` stops once every target is selected
+async fn enroll(ctx: &Context, until_done: bool) -> Result<()> {
+    let config = &ctx.config;
+    let mut enroll_config = config.enroll_config();
+    let round_interval = Duration::from_millis(config.enroll.round_interval_ms.unwrap_or(500));
+    let repeat = until_done || config.loop_mode.unwrap_or(false);
+    let scheduler = scheduler(ctx).await;
+    let cancel = cancel_on_signal();
+
+    let mut total_requests = 0;
+    let mut summary = Vec::new();
+    let mut round = 0;
+    loop {
+        let courses = cancel
+            .run_until_cancelled(async {
+                ctx.current().await?;
+                ctx.session.get_courses().await
+            })
+            .await;
+        let Some(courses) = courses else {
+            break;
+        };
+        let (selected, favorites) = courses?;
+        if !ctx.json {
+            print_courses(&selected, &favorites);
+        }
+
+        let targets = config.target_courses(&favorites, &selected);
+        if round == 0 {
+            for target in &targets.unresolved {
+                eprintln!("未在收藏或已选课程中找到: {target}");
+            }
+        }
+        if none_found(&config.courses, &config.groups, &targets.unresolved) {
+            return Err(
+                ErrorKind::CourseError("目标课程均未在收藏或已选课程中找到".to_string()).into(),
+            );
+        }
+        if !ctx.json {
+            print_preflight(&targets);
+        }
+        merge_outcomes(
+            &mut summary,
+            targets.already_selected.iter().map(|c| CourseOutcome {
+                class_id: c.JXBID.clone(),
+                name: c.KCM.clone(),
+                outcome: Some(EnrollOutcome::AlreadySelected),
+                confirmed: Some(true),
+                finished: true,
+            }),
+        );
+        enroll_config.groups = targets.groups;
+        let remaining = targets.courses;
+        if until_done && remaining.is_empty() {
+            // 未找到的目标无法选上，不能算作完成
+            if !targets.unresolved.is_empty() {
+                return Err(ErrorKind::CourseError(format!(
+                    "找到的目标课程已全部选上，但有 {} 个目标未找到",
+                    targets.unresolved.len()
+                ))
+                .into());
+            }
+            if !ctx.json {
+                println!("目标课程已全部选上");
+            }
+            break;
+        }
+
+        let result = match &scheduler {
+            Some(scheduler) if ctx.json => {
+                let engine = EnrollmentEngine::new(Arc::clone(&ctx.session), enroll_config.clone())
+                    .with_cancellation(cancel.child_token());
+                scheduler.run(&engine, &remaining).await
+            }
+            Some(scheduler) => {
+                enroll_courses_scheduled(scheduler, &remaining, &enroll_config, &cancel).await
+            }
+            None if ctx.json => {
+                EnrollmentEngine::new(Arc::clone(&ctx.session), enroll_config.clone())
+                    .with_cancellation(cancel.child_token())
+                    .run(&remaining)
+                    .await
+            }
+            None => {
+                enroll_courses_with_session(&ctx.session, &remaining, &enroll_config, &cancel).await
+            }
+        };
+        let report = match result {
+            Err(e) if matches!(e.kind(), ErrorKind::BatchEnded { .. }) => {
+                eprintln!("选课批次已结束: {e}");
+                break;
+            }
+            result => result?,
+        };
+        total_requests += report.total_requests;
+        merge_outcomes(&mut summary, report.courses);
+        ctx.save().await?;
+        if cancel.is_cancelled() {
+            break;
+        }
+
+        round += 1;
+        if !ctx.json {
+            let (selected, _) = ctx.session.get_courses().await?;
+            print_courses(&selected, &[]);
+
+            let throttle = ctx.limiter.metrics().total;
+            println!(
+                "限流: {}/{} 个请求等待, 共 {:?}, 最长 {:?}",
+                throttle.throttled, throttle.requests, throttle.total_wait, throttle.max_wait
+            );
+            println!("第 {round} 轮结束\n");
+        }
+
+        if !repeat {
             break;
         }
+        cancel
+            .run_until_cancelled(tokio::time::sleep(round_interval))
+            .await;
+    }
+
+    // JSON 与文本输出相同的汇总，包含之前轮次和已选上的课程
+    let report = EnrollReport {
+        total_requests,
+        courses: summary,
+    };
+    ctx.output(&report, || print_summary(&report.courses))
+}
+
+/// Replace the entries of `summary` for the same courses, keeping a
+/// selection from an earlier round
+fn merge_outcomes(
+    summary: &mut Vec<CourseOutcome>,
+    courses: impl IntoIterator<Item = CourseOutcome>,
+) {
+    for course in courses {
+        match summary.iter_mut().find(|c| c.class_id == course.class_id) {
+            Some(existing)
+                if existing.outcome.as_ref().is_some_and(|o| o.is_selected())
+                    && course.outcome == Some(EnrollOutcome::AlreadySelected) => {}
+            Some(existing) => *existing = course,
+            None => summary.push(course),
+        }
+    }
+}
+
+/// Cancel the returned token on the first SIGINT or SIGTERM, exit on the second
+fn cancel_on_signal() -> CancellationToken {
+    let cancel = CancellationToken::new();
+    let token = cancel.clone();
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        eprintln!("\n收到中断信号，正在停止选课，再次中断立即退出");
+        token.cancel();
+        shutdown_signal().await;
+        std::process::exit(130);
+    });
+    cancel
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        if let Ok(mut terminate) = signal(SignalKind::terminate()) {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => {}
+                _ = terminate.recv() => {}
+            }
+            return;
+        }
+    }
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+/// Some targets were given but none of them was found
+fn none_found(
+    courses: &[CourseTarget],
+    groups: &[Vec<CourseTarget>],
+    unresolved: &[CourseTarget],
+) -> bool {
+    let requested = courses.len() + groups.iter().map(Vec::len).sum::<usize>();
+    requested > 0 && unresolved.len() == requested
+}
+
+/// Enroll every account of `[[accounts]]` side by side under one rate limit
+async fn enroll_accounts(config: &AppConfig, until_done: bool, json: bool) -> Result<()> {
+    let limiter = Arc::new(config.rate_limiter());
+    let server = config.server.clone().unwrap_or_default();
+    // 每个账号的验证码图片分开保存，同一时间只提示一个
+    let solver = StdinSolver::default();
+    let mut passwords = VaultPasswords::new(config);
+    let mut pool = AccountPool::new(Arc::clone(&limiter), config.enroll_config());
+    for account in &config.accounts {
+        let Some(password) = passwords.password(account.password.as_ref(), &account.username)?
+        else {
+            return Err(ErrorKind::ParseError(format!(
+                "账号 {} 没有配置密码，也不在保险库中",
+                account.username
+            ))
+            .into());
+        };
+        let credentials = Credentials {
+            username: account.username.clone(),
+            password,
+        };
+        let batch = account
+            .batch
+            .clone()
+            .or_else(|| config.batch.clone())
+            .unwrap_or(BatchSelector::Auto);
+        pool.add_account(
+            server.clone(),
+            credentials,
+            batch,
+            account.courses.clone(),
+            account.groups.clone(),
+            Arc::new(solver.for_account(&account.username)),
+        )
+        .await?;
+    }
 
-        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    for account in pool.accounts() {
+        tokio::spawn(print_session_events(
+            account.session.subscribe(),
+            Some(account.username.clone()),
+        ));
     }
+    // 逐个登录，避免多个账号的验证码同时等待输入
+    let usernames: Vec<&str> = pool
+        .accounts()
+        .iter()
+        .map(|a| a.username.as_str())
+        .collect();
+    eprintln!("依次登录: {}", usernames.join(", "));
+    for (username, session) in usernames.iter().zip(pool.login_all().await) {
+        if let Err(e) = session {
+            eprintln!("[{username}] 登录失败");
+            return Err(e);
+        }
+    }
+    // 与单账号相同，某个账号的目标课程一个都没找到时不开始选课
+    for (account, resolution) in pool.accounts().iter().zip(pool.resolve_all().await) {
+        // 获取课程失败时留给选课轮次重试
+        let Ok(resolution) = resolution else {
+            continue;
+        };
+        if none_found(&account.courses, &account.groups, &resolution.unresolved) {
+            for target in &resolution.unresolved {
+                eprintln!("[{}] 未在收藏或已选课程中找到: {target}", account.username);
+            }
+            return Err(ErrorKind::CourseError(format!(
+                "账号 {} 的目标课程均未在收藏或已选课程中找到",
+                account.username
+            ))
+            .into());
+        }
+    }
+    let cancel = cancel_on_signal();
+    let pool = pool.with_cancellation(cancel.clone());
+
+    let printer = (!json).then(|| {
+        let mut events = pool.subscribe();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                match event {
+                    PoolEvent::Enroll {
+                        account,
+                        event:
+                            EnrollEvent::Attempt {
+                                name,
+                                outcome,
+                                total_requests,
+                                ..
+                            },
+                    } => println!(
+                        "[{account}] {name}: {} (#{total_requests})",
+                        outcome.label()
+                    ),
+                    PoolEvent::Enroll {
+                        account,
+                        event:
+                            EnrollEvent::Verified {
+                                name,
+                                confirmed: false,
+                                ..
+                            },
+                    } => println!("[{account}] {name}: 返回选课成功，但选课结果中未找到"),
+                    PoolEvent::Failed { account, error } => {
+                        eprintln!("[{account}] 本轮跳过: {error}")
+                    }
+                    PoolEvent::Enroll { .. } => {}
+                }
+            }
+        })
+    });
+
+    let round_interval = Duration::from_millis(config.enroll.round_interval_ms.unwrap_or(500));
+    let repeat = until_done || config.loop_mode.unwrap_or(false);
+    let mut round = 0;
+    let reports = loop {
+        let reports = pool.run().await;
+        round += 1;
+        if !json {
+            for report in &reports {
+                let selected = report.report.as_ref().map_or(0, |r| {
+                    r.courses
+                        .iter()
+                        .filter(|c| c.outcome.as_ref().is_some_and(|o| o.is_selected()))
+                        .count()
+                });
+                let total = report.report.as_ref().map_or(0, |r| r.courses.len());
+                match &report.error {
+                    Some(error) => println!("[{}] 失败: {error}", report.account),
+                    None => println!("[{}] 本轮选上 {selected}/{total}", report.account),
+                }
+                if round == 1 {
+                    for target in &report.unresolved {
+                        eprintln!("[{}] 未在收藏或已选课程中找到: {target}", report.account);
+                    }
+                    for same in &report.same_course_selected {
+                        println!(
+                            "[{}] 已选同一课程的其他教学班，跳过: {} (已选 {})",
+                            report.account, same.favorite.JXBID, same.selected.JXBID
+                        );
+                    }
+                }
+            }
+            let throttle = limiter.metrics().total;
+            println!(
+                "限流: {}/{} 个请求等待, 共 {:?}, 最长 {:?}",
+                throttle.throttled, throttle.requests, throttle.total_wait, throttle.max_wait
+            );
+            println!("第 {round} 轮结束\n");
+        }
 
+        if !repeat
+            || cancel.is_cancelled()
+            || (until_done && reports.iter().all(|r| r.is_finished()))
+        {
+            break reports;
+        }
+        cancel
+            .run_until_cancelled(tokio::time::sleep(round_interval))
+            .await;
+    };
+
+    drop(pool);
+    if let Some(printer) = printer {
+        let _ = printer.await;
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports)?);
+    } else {
+        for report in &reports {
+            println!("[{}]", report.account);
+            print_summary(report.report.as_ref().map_or(&[], |r| &r.courses));
+        }
+    }
+    let failed: Vec<&str> = reports
+        .iter()
+        .filter(|r| r.error.is_some() || !r.unresolved.is_empty())
+        .map(|r| r.account.as_str())
+        .collect();
+    if !failed.is_empty() {
+        return Err(ErrorKind::CourseError(format!(
+            "以下账号失败或有目标未找到: {}",
+            failed.join(", ")
+        ))
+        .into());
+    }
     Ok(())
 }
+
+/// Scheduler for `--schedule`, corrected by the measured server clock offset
+async fn scheduler(ctx: &Context) -> Option<Scheduler> {
+    let schedule = ctx.config.schedule.schedule_config()?;
+    let mut scheduler = Scheduler::new(Arc::clone(&ctx.session), schedule);
+
+    let clock_samples = ctx.config.schedule.clock_samples.unwrap_or(5);
+    if clock_samples > 0 {
+        match ClockSync::measure(ctx.session.client(), clock_samples).await {
+            Ok(sync) => {
+                eprintln!(
+                    "服务器时钟偏差: {} ms (往返 {:?})",
+                    sync.offset().num_milliseconds(),
+                    sync.rtt()
+                );
+                scheduler = scheduler.with_clock_offset(sync.offset());
+            }
+            Err(e) => eprintln!("无法校准服务器时钟，使用本地时间: {e}"),
+        }
+    }
+    Some(scheduler)
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let mut config = cli.global.app_config()?;
+    if let Command::Enroll(args) | Command::Watch(args) = &cli.command {
+        args.apply(&mut config);
+    }
+    if config.enroll.workers == Some(0) {
+        return Err(ErrorKind::ParseError("workers must be at least 1".to_string()).into());
+    }
+
+    if let Command::Vault { action } = cli.command {
+        return vault(&config, action, cli.global.json);
+    }
+    if !config.accounts.is_empty()
+        && let Command::Enroll(_) | Command::Watch(_) = &cli.command
+    {
+        let until_done = matches!(cli.command, Command::Watch(_));
+        return enroll_accounts(&config, until_done, cli.global.json).await;
+    }
+
+    let ctx = Context::new(&cli.global, config).await?;
+    match cli.command {
+        Command::Login => login(&ctx).await,
+        Command::Batches => batches(&ctx).await,
+        Command::Courses { list } => courses(&ctx, list).await,
+        Command::Enroll(_) => enroll(&ctx, false).await,
+        Command::Watch(_) => enroll(&ctx, true).await,
+        Command::Status => status(&ctx).await,
+        Command::Vault { .. } => unreachable!(),
+    }
+}
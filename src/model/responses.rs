@@ -0,0 +1,61 @@
+use crate::error::{ErrorKind, Result};
+use crate::model::structs::{BatchInfo, CourseInfo};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// `{code, msg, data}` envelope returned by every icourses endpoint
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ApiResponse<T = Value> {
+    pub code: i64,
+    #[serde(default)]
+    pub msg: String,
+    #[serde(default = "Option::default")]
+    pub data: Option<T>,
+}
+
+impl<T> ApiResponse<T> {
+    pub fn is_success(&self) -> bool {
+        self.code == 200
+    }
+
+    /// Unwrap the payload of a successful response
+    pub fn into_data(self) -> Result<T> {
+        if self.code == 401 {
+            return Err(ErrorKind::TokenExpired.into());
+        }
+        if !self.is_success() {
+            return Err(ErrorKind::CourseError(self.msg).into());
+        }
+        self.data
+            .ok_or_else(|| ErrorKind::ParseError("Missing response data".to_string()).into())
+    }
+}
+
+/// Payload of `auth/captcha`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CaptchaData {
+    pub uuid: String,
+    /// `data:image/png;base64,...` image
+    pub captcha: String,
+}
+
+/// Payload of a successful `auth/login`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LoginData {
+    pub token: String,
+    pub student: StudentInfo,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[allow(non_snake_case)] // API字段名与服务器保持一致
+pub struct StudentInfo {
+    pub XH: String,   // 学号
+    pub XM: String,   // 姓名
+    pub ZYMC: String, // 专业名称
+    #[serde(rename = "electiveBatchList")]
+    pub elective_batch_list: Vec<BatchInfo>,
+}
+
+pub type CaptchaResponse = ApiResponse<CaptchaData>;
+pub type LoginResponse = ApiResponse<LoginData>;
+pub type CourseListResponse = ApiResponse<Vec<CourseInfo>>;
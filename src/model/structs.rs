@@ -1,3 +1,5 @@
+use crate::error::Result;
+use crate::model::responses::ApiResponse;
 use serde::{Deserialize, Serialize};
 
 // Common data structures used across all platforms
@@ -11,6 +13,192 @@ pub struct BatchInfo {
     pub end_time: String,
 }
 
+/// Which entry of `electiveBatchList` to select
+///
+/// In config files a batch is given as an index, `{ code = ".." }`,
+/// `{ name = ".." }` or a string parsed like the `--batch` flag.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(from = "BatchSelectorRepr", into = "BatchSelectorRepr")]
+pub enum BatchSelector {
+    /// Position in the batch list, starting at 0
+    Index(usize),
+    Code(String),
+    /// Part of the batch name
+    Name(String),
+    /// The batch open right now, else the next one to open
+    Auto,
+}
+
+impl std::str::FromStr for BatchSelector {
+    type Err = std::convert::Infallible;
+
+    /// `auto`, an index, `name:<part of name>`, or a batch code with optional `code:`
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        Ok(if s.eq_ignore_ascii_case("auto") {
+            Self::Auto
+        } else if let Ok(idx) = s.parse() {
+            Self::Index(idx)
+        } else if let Some(name) = s.strip_prefix("name:") {
+            Self::Name(name.trim().to_string())
+        } else {
+            Self::Code(s.strip_prefix("code:").unwrap_or(s).trim().to_string())
+        })
+    }
+}
+
+impl From<usize> for BatchSelector {
+    fn from(idx: usize) -> Self {
+        Self::Index(idx)
+    }
+}
+
+impl std::fmt::Display for BatchSelector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Index(idx) => write!(f, "#{idx}"),
+            Self::Code(code) => write!(f, "code {code}"),
+            Self::Name(name) => write!(f, "name containing {name:?}"),
+            Self::Auto => write!(f, "auto"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+enum BatchSelectorRepr {
+    Index(usize),
+    Text(String),
+    Code { code: String },
+    Name { name: String },
+}
+
+impl From<BatchSelectorRepr> for BatchSelector {
+    fn from(repr: BatchSelectorRepr) -> Self {
+        match repr {
+            BatchSelectorRepr::Index(idx) => Self::Index(idx),
+            BatchSelectorRepr::Text(text) => {
+                let Ok(selector) = text.parse();
+                selector
+            }
+            BatchSelectorRepr::Code { code } => Self::Code(code),
+            BatchSelectorRepr::Name { name } => Self::Name(name),
+        }
+    }
+}
+
+impl From<BatchSelector> for BatchSelectorRepr {
+    fn from(selector: BatchSelector) -> Self {
+        match selector {
+            BatchSelector::Index(idx) => Self::Index(idx),
+            BatchSelector::Code(code) => Self::Code { code },
+            BatchSelector::Name(name) => Self::Name { name },
+            BatchSelector::Auto => Self::Text("auto".to_string()),
+        }
+    }
+}
+
+/// A course to enroll in, looked up in the favorites
+///
+/// In config files a target is a string matching the JXBID, course name or
+/// teacher, or a table such as `{ name = "高等数学", teacher = "张老师" }`
+/// whose fields must all match.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CourseTarget {
+    /// JXBID, course name or teacher
+    Any(String),
+    Match(CourseFilter),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CourseFilter {
+    /// JXBID
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// KCM
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// SKJS
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub teacher: Option<String>,
+}
+
+impl CourseTarget {
+    /// An empty filter matches nothing
+    pub fn matches(&self, course: &CourseInfo) -> bool {
+        match self {
+            Self::Any(s) => *s == course.JXBID || *s == course.KCM || *s == course.SKJS,
+            Self::Match(filter) => {
+                let fields = [
+                    (&filter.id, &course.JXBID),
+                    (&filter.name, &course.KCM),
+                    (&filter.teacher, &course.SKJS),
+                ];
+                fields.iter().any(|(want, _)| want.is_some())
+                    && fields
+                        .iter()
+                        .all(|(want, have)| want.as_ref().is_none_or(|w| w == *have))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for CourseTarget {
+    type Err = std::convert::Infallible;
+
+    /// `id:`, `name:` or `teacher:` to match one field, otherwise any of them
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let s = s.trim();
+        let field = |prefix: &str| s.strip_prefix(prefix).map(|v| Some(v.trim().to_string()));
+        Ok(if let Some(id) = field("id:") {
+            Self::Match(CourseFilter {
+                id,
+                ..CourseFilter::default()
+            })
+        } else if let Some(name) = field("name:") {
+            Self::Match(CourseFilter {
+                name,
+                ..CourseFilter::default()
+            })
+        } else if let Some(teacher) = field("teacher:") {
+            Self::Match(CourseFilter {
+                teacher,
+                ..CourseFilter::default()
+            })
+        } else {
+            Self::Any(s.to_string())
+        })
+    }
+}
+
+impl From<&str> for CourseTarget {
+    fn from(s: &str) -> Self {
+        Self::Any(s.to_string())
+    }
+}
+
+impl std::fmt::Display for CourseTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any(s) => write!(f, "{s}"),
+            Self::Match(filter) => {
+                let fields = [
+                    ("id", &filter.id),
+                    ("name", &filter.name),
+                    ("teacher", &filter.teacher),
+                ];
+                let parts: Vec<String> = fields
+                    .iter()
+                    .filter_map(|(key, value)| value.as_ref().map(|v| format!("{key}:{v}")))
+                    .collect();
+                write!(f, "{}", parts.join(" "))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 #[allow(non_snake_case)] // API字段名与服务器保持一致
 pub struct CourseInfo {
@@ -23,9 +211,128 @@ pub struct CourseInfo {
     pub secret_val: Option<String>,
 }
 
+/// Classified result of a single `addxk` request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum EnrollOutcome {
+    Success,
+    AlreadySelected,
+    NotStarted,
+    CapacityFull,
+    ParamInvalid,
+    Unauthorized,
+    Unknown {
+        code: i64,
+        msg: String,
+    },
+    Transport {
+        message: String,
+    },
+    /// Not requested because `class_id` of the same group was selected
+    AlternativeSelected {
+        class_id: String,
+    },
+    /// Rejected with `参数校验不通过` and no longer in the favorites
+    RemovedFromFavorites,
+}
+
+impl EnrollOutcome {
+    /// Classify a server reply by its `code` and `msg`
+    pub fn from_response(code: i64, msg: &str) -> Self {
+        match (code, msg) {
+            (200, _) => Self::Success,
+            (500, "该课程已在选课结果中") => Self::AlreadySelected,
+            (500, "本轮次选课暂未开始") => Self::NotStarted,
+            (500, "课容量已满") => Self::CapacityFull,
+            (500, "参数校验不通过") => Self::ParamInvalid,
+            (401, _) => Self::Unauthorized,
+            _ => Self::Unknown {
+                code,
+                msg: msg.to_string(),
+            },
+        }
+    }
+
+    /// Classify the result of `select_course`, including transport failures
+    pub fn classify(result: &Result<ApiResponse>) -> Self {
+        match result {
+            Ok(resp) => Self::from_response(resp.code, &resp.msg),
+            Err(e) if e.is_token_expired() => Self::Unauthorized,
+            Err(e) => Self::Transport {
+                message: e.to_string(),
+            },
+        }
+    }
+
+    /// The course is in the selected list after this outcome
+    pub fn is_selected(&self) -> bool {
+        matches!(self, Self::Success | Self::AlreadySelected)
+    }
+
+    /// Short display label
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Success => "选课成功",
+            Self::AlreadySelected => "已选",
+            Self::NotStarted => "未开始",
+            Self::CapacityFull => "已满",
+            Self::ParamInvalid => "参数错误",
+            Self::Unauthorized => "未登录",
+            Self::Unknown { .. } => "失败",
+            Self::Transport { .. } => "请求错误",
+            Self::AlternativeSelected { .. } => "已选同组课程",
+            Self::RemovedFromFavorites => "已不在收藏中",
+        }
+    }
+}
+
+/// Latest enrollment outcome of one course, `None` before the first attempt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CourseOutcome {
+    pub class_id: String,
+    pub name: String,
+    pub outcome: Option<EnrollOutcome>,
+    /// Whether the selected list was checked to contain the course,
+    /// `None` if it was never checked
+    #[serde(default)]
+    pub confirmed: Option<bool>,
+    /// Retrying cannot change `outcome` any more
+    #[serde(default)]
+    pub finished: bool,
+}
+
+/// Why an enrollment run ended
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum CompletionReason {
+    /// Every course reached a final outcome
+    AllFinished,
+    /// The run was stopped before that
+    Stopped,
+    Failed {
+        message: String,
+    },
+}
+
+impl CompletionReason {
+    /// Short display label
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::AllFinished => "全部课程已完成",
+            Self::Stopped => "已停止",
+            Self::Failed { .. } => "选课出错",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct EnrollmentStatus {
     pub total_requests: u32,
     pub course_statuses: Vec<String>,
+    #[serde(default)]
+    pub course_outcomes: Vec<CourseOutcome>,
     pub is_running: bool,
+    /// Set once the run is over
+    #[serde(default)]
+    pub completion: Option<CompletionReason>,
 }
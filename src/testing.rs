@@ -0,0 +1,600 @@
+//! Mock icourses server for offline integration tests
+//!
+//! [`MockServer`] runs an in-process axum server implementing the endpoints
+//! used by [`RequestApi`](crate::interface::RequestApi). Point a client at it
+//! with [`MockServer::server_config`] and script `addxk` replies per course.
+
+use crate::app::captcha::CaptchaSolver;
+use crate::app::session::{Credentials, SessionManager};
+use crate::client::config::ServerConfig;
+use crate::client::request::NoWasmClient;
+use crate::crypto;
+use crate::error::Result;
+use crate::interface::HttpClient;
+use crate::model::structs::{BatchInfo, CourseInfo};
+use axum::{
+    Json, Router,
+    extract::{Query, State},
+    http::{HeaderMap, HeaderValue, StatusCode, header},
+    middleware,
+    response::{Html, IntoResponse, Response},
+    routing::{any, get, post},
+};
+use chrono::{TimeDelta, Utc};
+use futures::future::BoxFuture;
+use serde_json::{Value, json};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::task::JoinHandle;
+
+/// AES key embedded in the mock index page
+pub const MOCK_AES_KEY: &str = "MWMqg2tPcDkxcm11";
+/// Default account accepted by the mock login endpoint
+pub const MOCK_USERNAME: &str = "20240001";
+pub const MOCK_PASSWORD: &str = "mock-password";
+/// UUID returned with every mock captcha
+pub const MOCK_CAPTCHA_UUID: &str = "00000000-0000-0000-0000-000000000000";
+
+/// Captcha solver answering every captcha with `1234`, counting its calls
+///
+/// Clones share the count.
+#[derive(Debug, Clone, Default)]
+pub struct MockSolver {
+    calls: Arc<AtomicUsize>,
+}
+
+impl MockSolver {
+    /// Captchas solved so far
+    pub fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl CaptchaSolver for MockSolver {
+    fn solve(&self, _image: Vec<u8>, _uuid: String) -> BoxFuture<'_, Result<String>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async { Ok("1234".to_string()) })
+    }
+}
+
+/// Empty directory `funky-<name>-<pid>` in the system temp dir, cleared of
+/// whatever an earlier run left there
+pub fn scratch_dir(name: &str) -> Result<PathBuf> {
+    let dir = std::env::temp_dir().join(format!("funky-{name}-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir)?;
+    Ok(dir)
+}
+
+/// Scripted `addxk` reply
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MockReply {
+    pub code: i64,
+    pub msg: String,
+}
+
+impl MockReply {
+    pub fn new(code: i64, msg: impl Into<String>) -> Self {
+        Self {
+            code,
+            msg: msg.into(),
+        }
+    }
+
+    pub fn success() -> Self {
+        Self::new(200, "选课成功")
+    }
+
+    pub fn already_selected() -> Self {
+        Self::new(500, "该课程已在选课结果中")
+    }
+
+    pub fn not_started() -> Self {
+        Self::new(500, "本轮次选课暂未开始")
+    }
+
+    pub fn capacity_full() -> Self {
+        Self::new(500, "课容量已满")
+    }
+
+    pub fn param_invalid() -> Self {
+        Self::new(500, "参数校验不通过")
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(401, "请先登录")
+    }
+}
+
+#[derive(Debug)]
+struct MockState {
+    /// Password of every account that can log in
+    accounts: HashMap<String, String>,
+    /// Expected captcha answer, `None` accepts anything
+    captcha: Option<String>,
+    tokens: HashSet<String>,
+    issued_tokens: u32,
+    current_batch: Option<String>,
+    batches: Vec<BatchInfo>,
+    favorites: Vec<CourseInfo>,
+    selected: Vec<CourseInfo>,
+    scripts: HashMap<String, VecDeque<MockReply>>,
+    enroll_attempts: HashMap<String, usize>,
+    /// `addxk` requests per course that select it but fail to reply
+    lost_replies: HashMap<String, usize>,
+    /// HTTP status of `addxk` error replies, 200 when unset
+    error_status: Option<StatusCode>,
+    /// Added to the `Date` header of every response
+    clock_skew: TimeDelta,
+    /// Responses still to be sent with an unparsable `Date` header
+    broken_dates: usize,
+    /// `Cookie` header of the latest selected-courses request
+    last_cookie: Option<String>,
+}
+
+impl Default for MockState {
+    fn default() -> Self {
+        Self {
+            accounts: HashMap::from([(MOCK_USERNAME.to_string(), MOCK_PASSWORD.to_string())]),
+            captcha: None,
+            tokens: HashSet::new(),
+            issued_tokens: 0,
+            current_batch: None,
+            batches: vec![
+                mock_batch(
+                    "batch-1",
+                    "第一轮选课",
+                    "2025-08-20 13:00:00",
+                    "2025-08-22 17:00:00",
+                ),
+                mock_batch(
+                    "batch-2",
+                    "第二轮选课",
+                    "2025-08-25 13:00:00",
+                    "2025-08-27 17:00:00",
+                ),
+            ],
+            favorites: vec![
+                mock_course("class-1", "高等数学", "张老师"),
+                mock_course("class-2", "大学物理", "李老师"),
+            ],
+            selected: Vec::new(),
+            scripts: HashMap::new(),
+            enroll_attempts: HashMap::new(),
+            lost_replies: HashMap::new(),
+            error_status: None,
+            clock_skew: TimeDelta::zero(),
+            broken_dates: 0,
+            last_cookie: None,
+        }
+    }
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+/// In-process mock icourses server, shut down on drop
+#[derive(Debug)]
+pub struct MockServer {
+    addr: SocketAddr,
+    state: SharedState,
+    handle: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Start a mock server on a random local port with the default fixtures
+    pub async fn start() -> Result<Self> {
+        let state: SharedState = Arc::default();
+
+        let router = Router::new()
+            .route("/", get(index))
+            .route("/xsxk/profile/index.html", get(index))
+            .route("/xsxk/auth/captcha", post(captcha))
+            .route("/xsxk/auth/login", post(login))
+            .route("/xsxk/elective/user", post(elective_user))
+            .route("/xsxk/elective/grablessons", any(grablessons))
+            .route("/xsxk/elective/select", post(selected_list))
+            .route("/xsxk/sc/clazz/list", post(favorite_list))
+            .route("/xsxk/sc/clazz/addxk", post(add_course))
+            .layer(middleware::map_response_with_state(
+                Arc::clone(&state),
+                date_header,
+            ))
+            .with_state(Arc::clone(&state));
+
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await?;
+        let addr = listener.local_addr()?;
+        let handle = tokio::spawn(async move {
+            let _ = axum::serve(listener, router).await;
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            handle,
+        })
+    }
+
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Server configuration pointing at this mock
+    pub fn server_config(&self) -> ServerConfig {
+        ServerConfig::new(self.base_url())
+    }
+
+    /// Session manager for [`MOCK_USERNAME`] on a fresh client, selecting the
+    /// first batch and answering captchas with `solver`
+    pub async fn session_manager(
+        &self,
+        solver: impl CaptchaSolver + 'static,
+    ) -> Result<Arc<SessionManager>> {
+        let client = NoWasmClient::with_config(self.server_config()).await?;
+        Ok(Self::session_manager_with(client, solver))
+    }
+
+    /// Like [`session_manager`](Self::session_manager) on an existing `client`
+    pub fn session_manager_with(
+        client: NoWasmClient,
+        solver: impl CaptchaSolver + 'static,
+    ) -> Arc<SessionManager> {
+        let credentials = Credentials {
+            username: MOCK_USERNAME.to_string(),
+            password: MOCK_PASSWORD.to_string(),
+        };
+        Arc::new(SessionManager::new(
+            client,
+            credentials,
+            0,
+            Arc::new(solver),
+        ))
+    }
+
+    /// Accept another account besides [`MOCK_USERNAME`]
+    pub fn add_account(&self, username: impl Into<String>, password: impl Into<String>) {
+        self.lock()
+            .accounts
+            .insert(username.into(), password.into());
+    }
+
+    /// Require this captcha answer on login
+    pub fn set_captcha(&self, captcha: impl Into<String>) {
+        self.lock().captcha = Some(captcha.into());
+    }
+
+    pub fn set_batches(&self, batches: Vec<BatchInfo>) {
+        self.lock().batches = batches;
+    }
+
+    pub fn set_favorites(&self, courses: Vec<CourseInfo>) {
+        self.lock().favorites = courses;
+    }
+
+    pub fn set_selected(&self, courses: Vec<CourseInfo>) {
+        self.lock().selected = courses;
+    }
+
+    /// Queue `addxk` replies for a course; once drained the course is selected
+    pub fn script_enroll(&self, class_id: &str, replies: impl IntoIterator<Item = MockReply>) {
+        self.lock()
+            .scripts
+            .entry(class_id.to_string())
+            .or_default()
+            .extend(replies);
+    }
+
+    /// Select the course on its next `count` `addxk` requests but answer with a 502
+    pub fn lose_enroll_replies(&self, class_id: &str, count: usize) {
+        *self
+            .lock()
+            .lost_replies
+            .entry(class_id.to_string())
+            .or_default() += count;
+    }
+
+    /// Send `addxk` error envelopes with `status` instead of 200
+    pub fn set_error_status(&self, status: u16) {
+        self.lock().error_status = StatusCode::from_u16(status).ok();
+    }
+
+    /// Run the server clock ahead of the local clock by `skew`
+    pub fn set_clock_skew(&self, skew: TimeDelta) {
+        self.lock().clock_skew = skew;
+    }
+
+    /// Send the next `count` responses with an unparsable `Date` header
+    pub fn break_dates(&self, count: usize) {
+        self.lock().broken_dates = count;
+    }
+
+    /// Invalidate every issued token
+    pub fn expire_tokens(&self) {
+        self.lock().tokens.clear();
+    }
+
+    /// Number of `addxk` requests received for a course
+    pub fn enroll_attempts(&self, class_id: &str) -> usize {
+        self.lock()
+            .enroll_attempts
+            .get(class_id)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Number of successful logins so far
+    pub fn login_count(&self) -> u32 {
+        self.lock().issued_tokens
+    }
+
+    pub fn current_batch(&self) -> Option<String> {
+        self.lock().current_batch.clone()
+    }
+
+    pub fn selected(&self) -> Vec<CourseInfo> {
+        self.lock().selected.clone()
+    }
+
+    /// `Cookie` header sent with the latest selected-courses request
+    pub fn last_cookie(&self) -> Option<String> {
+        self.lock().last_cookie.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, MockState> {
+        self.state.lock().unwrap()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.handle.abort();
+    }
+}
+
+/// Build a batch fixture
+pub fn mock_batch(code: &str, name: &str, begin_time: &str, end_time: &str) -> BatchInfo {
+    BatchInfo {
+        code: code.to_string(),
+        name: name.to_string(),
+        begin_time: begin_time.to_string(),
+        end_time: end_time.to_string(),
+    }
+}
+
+/// Build a course fixture
+pub fn mock_course(class_id: &str, name: &str, teacher: &str) -> CourseInfo {
+    CourseInfo {
+        SKJS: teacher.to_string(),
+        KCM: name.to_string(),
+        JXBID: class_id.to_string(),
+        teaching_class_type: Some("XGKC".to_string()),
+        secret_val: Some(format!("secret-{class_id}")),
+    }
+}
+
+fn reply(code: i64, msg: &str, data: Value) -> Json<Value> {
+    Json(json!({ "code": code, "msg": msg, "data": data }))
+}
+
+fn unauthorized() -> Json<Value> {
+    let MockReply { code, msg } = MockReply::unauthorized();
+    reply(code, &msg, Value::Null)
+}
+
+fn authorized(state: &MockState, headers: &HeaderMap) -> bool {
+    headers
+        .get("Authorization")
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|token| state.tokens.contains(token))
+}
+
+/// Stamp the skewed server time into the `Date` header
+async fn date_header(State(state): State<SharedState>, mut response: Response) -> Response {
+    let date = {
+        let mut state = state.lock().unwrap();
+        if state.broken_dates > 0 {
+            state.broken_dates -= 1;
+            "not a date".to_string()
+        } else {
+            let now = Utc::now() + state.clock_skew;
+            now.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+        }
+    };
+    if let Ok(value) = HeaderValue::from_str(&date) {
+        response.headers_mut().insert(header::DATE, value);
+    }
+    response
+}
+
+async fn index() -> Html<String> {
+    Html(format!(
+        "<html><script>loginVue.loginForm.aesKey = \"{MOCK_AES_KEY}\";</script></html>"
+    ))
+}
+
+async fn captcha() -> Json<Value> {
+    reply(
+        200,
+        "操作成功",
+        json!({
+            "uuid": MOCK_CAPTCHA_UUID,
+            "captcha": "data:image/png;base64,iVBORw0KGgo=",
+        }),
+    )
+}
+
+async fn login(
+    State(state): State<SharedState>,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let param = |key: &str| params.get(key).map(String::as_str).unwrap_or_default();
+
+    if let Some(expected) = &state.captcha
+        && param("captcha") != expected
+    {
+        return reply(500, "验证码错误", Value::Null).into_response();
+    }
+
+    let username = param("loginname").to_string();
+    let expected_password = state
+        .accounts
+        .get(&username)
+        .and_then(|password| crypto::encrypt_password(password, MOCK_AES_KEY.as_bytes()).ok());
+    if expected_password.as_deref() != Some(param("password")) {
+        return reply(500, "用户名或密码错误", Value::Null).into_response();
+    }
+
+    state.issued_tokens += 1;
+    let token = format!("mock-token-{}", state.issued_tokens);
+    state.tokens.insert(token.clone());
+    let cookie = format!("JSESSIONID=mock-session-{}; Path=/", state.issued_tokens);
+
+    let body = reply(
+        200,
+        "登录成功",
+        json!({
+            "token": token,
+            "student": {
+                "XH": username,
+                "XM": "测试学生",
+                "ZYMC": "计算机科学与技术",
+                "electiveBatchList": state.batches,
+            },
+        }),
+    );
+    ([(header::SET_COOKIE, cookie)], body).into_response()
+}
+
+async fn elective_user(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let mut state = state.lock().unwrap();
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+
+    let batch_id = params.get("batchId").cloned().unwrap_or_default();
+    if !state.batches.iter().any(|b| b.code == batch_id) {
+        return reply(500, "选课批次不存在", Value::Null);
+    }
+
+    state.current_batch = Some(batch_id);
+    reply(200, "操作成功", Value::Null)
+}
+
+async fn grablessons() -> Html<&'static str> {
+    Html("<html></html>")
+}
+
+async fn selected_list(State(state): State<SharedState>, headers: HeaderMap) -> Json<Value> {
+    let mut state = state.lock().unwrap();
+    state.last_cookie = headers
+        .get(header::COOKIE)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+    reply(200, "操作成功", json!(state.selected))
+}
+
+async fn favorite_list(State(state): State<SharedState>, headers: HeaderMap) -> Json<Value> {
+    let state = state.lock().unwrap();
+    if !authorized(&state, &headers) {
+        return unauthorized();
+    }
+    reply(200, "操作成功", json!(state.favorites))
+}
+
+async fn add_course(
+    State(state): State<SharedState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Response {
+    let mut state = state.lock().unwrap();
+    let class_id = params.get("clazzId").cloned().unwrap_or_default();
+    // 服务器已选上课程，但响应丢失
+    if authorized(&state, &headers)
+        && let Some(lost) = state.lost_replies.get_mut(&class_id)
+        && *lost > 0
+    {
+        *lost -= 1;
+        *state.enroll_attempts.entry(class_id.clone()).or_default() += 1;
+        select(&mut state, &class_id);
+        return (StatusCode::BAD_GATEWAY, "Bad Gateway").into_response();
+    }
+    let body = enroll(&mut state, &headers, &params);
+    match state.error_status {
+        Some(status) if body["code"] != 200 => (status, body).into_response(),
+        _ => body.into_response(),
+    }
+}
+
+fn enroll(
+    state: &mut MockState,
+    headers: &HeaderMap,
+    params: &HashMap<String, String>,
+) -> Json<Value> {
+    let class_id = params.get("clazzId").cloned().unwrap_or_default();
+    *state.enroll_attempts.entry(class_id.clone()).or_default() += 1;
+
+    if !authorized(state, headers) {
+        return unauthorized();
+    }
+
+    // 收藏中的 secretVal 已更换时，旧值校验不通过
+    let secret_val = params.get("secretVal").map(String::as_str);
+    if state
+        .favorites
+        .iter()
+        .find(|c| c.JXBID == class_id)
+        .is_some_and(|c| c.secret_val.is_some() && c.secret_val.as_deref() != secret_val)
+    {
+        let MockReply { code, msg } = MockReply::param_invalid();
+        return reply(code, &msg, Value::Null);
+    }
+
+    if let Some(MockReply { code, msg }) = state
+        .scripts
+        .get_mut(&class_id)
+        .and_then(VecDeque::pop_front)
+    {
+        if code == 200 {
+            select(state, &class_id);
+        }
+        return reply(code, &msg, Value::Null);
+    }
+
+    if state.selected.iter().any(|c| c.JXBID == class_id) {
+        let MockReply { code, msg } = MockReply::already_selected();
+        return reply(code, &msg, Value::Null);
+    }
+
+    if !select(state, &class_id) {
+        let MockReply { code, msg } = MockReply::param_invalid();
+        return reply(code, &msg, Value::Null);
+    }
+
+    let MockReply { code, msg } = MockReply::success();
+    reply(code, &msg, Value::Null)
+}
+
+/// Move a favorite course into the selected list
+fn select(state: &mut MockState, class_id: &str) -> bool {
+    if state.selected.iter().any(|c| c.JXBID == class_id) {
+        return true;
+    }
+    match state.favorites.iter().find(|c| c.JXBID == class_id) {
+        Some(course) => {
+            let course = course.clone();
+            state.selected.push(course);
+            true
+        }
+        None => false,
+    }
+}
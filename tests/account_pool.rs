@@ -0,0 +1,145 @@
+//! Tests for enrolling several accounts in one process
+
+use funky_lesson_core::app::engine::{EnrollConfig, EnrollEvent};
+use funky_lesson_core::app::pool::{AccountPool, PoolEvent};
+use funky_lesson_core::app::session::Credentials;
+use funky_lesson_core::client::rate_limit::{Endpoint, RateLimiter};
+use funky_lesson_core::error::Result;
+use funky_lesson_core::model::structs::EnrollOutcome;
+use funky_lesson_core::testing::{MOCK_PASSWORD, MOCK_USERNAME, MockReply, MockServer, MockSolver};
+use std::collections::HashSet;
+use std::sync::Arc;
+
+const OTHER_USERNAME: &str = "20240002";
+const OTHER_PASSWORD: &str = "other-password";
+
+fn credentials(username: &str, password: &str) -> Credentials {
+    Credentials {
+        username: username.to_string(),
+        password: password.to_string(),
+    }
+}
+
+async fn pool(server: &MockServer, accounts: &[(&str, &str, &str)]) -> Result<AccountPool> {
+    let limiter = Arc::new(RateLimiter::new(None));
+    let config = EnrollConfig {
+        workers: 1,
+        ..EnrollConfig::default()
+    };
+    let mut pool = AccountPool::new(limiter, config);
+    for (username, password, course) in accounts {
+        pool.add_account(
+            server.server_config(),
+            credentials(username, password),
+            1,
+            vec![(*course).into()],
+            Vec::new(),
+            Arc::new(MockSolver::default()),
+        )
+        .await?;
+    }
+    Ok(pool)
+}
+
+#[tokio::test]
+async fn accounts_enroll_side_by_side_with_own_sessions() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.add_account(OTHER_USERNAME, OTHER_PASSWORD);
+    server.script_enroll("class-2", [MockReply::capacity_full()]);
+    let pool = pool(
+        &server,
+        &[
+            (MOCK_USERNAME, MOCK_PASSWORD, "class-1"),
+            (OTHER_USERNAME, OTHER_PASSWORD, "大学物理"),
+        ],
+    )
+    .await?;
+    let mut events = pool.subscribe();
+
+    let reports = pool.run().await;
+    assert_eq!(server.login_count(), 2);
+    assert_eq!(reports.len(), 2);
+    assert!(reports.iter().all(|r| r.error.is_none() && r.is_done()));
+    assert!(reports.iter().all(|r| r.unresolved.is_empty()));
+    assert_eq!(reports[0].account, MOCK_USERNAME);
+    assert_eq!(
+        reports[0].report.as_ref().unwrap().courses[0].class_id,
+        "class-1"
+    );
+    assert_eq!(
+        reports[1].report.as_ref().unwrap().courses[0].class_id,
+        "class-2"
+    );
+
+    // 每个账号有自己的 token 和 cookie
+    let mut tokens = HashSet::new();
+    let mut cookies = HashSet::new();
+    for account in pool.accounts() {
+        tokens.insert(account.session.current().await?.token);
+        let jar = account.session.client().cookies();
+        cookies.insert(
+            jar.iter()
+                .map(|c| c.value().to_string())
+                .collect::<Vec<_>>(),
+        );
+    }
+    assert_eq!(tokens.len(), 2);
+    assert_eq!(cookies.len(), 2);
+
+    // 所有账号共用同一个限流器
+    let metrics = pool.limiter().metrics();
+    assert_eq!(metrics.endpoints[&Endpoint::Login].requests, 2);
+    assert_eq!(metrics.endpoints[&Endpoint::AddCourse].requests, 3);
+
+    drop(pool);
+    let mut attempts = Vec::new();
+    while let Some(event) = events.recv().await {
+        if let PoolEvent::Enroll {
+            account,
+            event: EnrollEvent::Attempt { outcome, .. },
+        } = event
+        {
+            attempts.push((account, outcome));
+        }
+    }
+    assert!(attempts.contains(&(MOCK_USERNAME.to_string(), EnrollOutcome::Success)));
+    assert!(attempts.contains(&(OTHER_USERNAME.to_string(), EnrollOutcome::CapacityFull)));
+    assert!(attempts.contains(&(OTHER_USERNAME.to_string(), EnrollOutcome::Success)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn failing_account_does_not_stop_the_others() -> Result<()> {
+    let server = MockServer::start().await?;
+    let pool = pool(
+        &server,
+        &[
+            (MOCK_USERNAME, MOCK_PASSWORD, "class-1"),
+            (OTHER_USERNAME, "wrong-password", "class-2"),
+        ],
+    )
+    .await?;
+    let mut events = pool.subscribe();
+
+    let reports = pool.run().await;
+    assert!(reports[0].is_done());
+    assert!(reports[1].report.is_none());
+    assert!(
+        reports[1]
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("用户名或密码错误")
+    );
+    assert_eq!(server.enroll_attempts("class-2"), 0);
+
+    drop(pool);
+    let mut failed = Vec::new();
+    while let Some(event) = events.recv().await {
+        if let PoolEvent::Failed { account, .. } = event {
+            failed.push(account);
+        }
+    }
+    assert_eq!(failed, vec![OTHER_USERNAME.to_string()]);
+    Ok(())
+}
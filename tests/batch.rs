@@ -0,0 +1,93 @@
+//! Tests for choosing an elective batch
+
+use funky_lesson_core::app::find_batch;
+use funky_lesson_core::app::scheduler::parse_batch_time;
+use funky_lesson_core::error::ErrorKind;
+use funky_lesson_core::model::structs::{BatchInfo, BatchSelector};
+use funky_lesson_core::testing::mock_batch;
+
+fn batches() -> Vec<BatchInfo> {
+    vec![
+        mock_batch(
+            "b1",
+            "2025秋 第一轮选课",
+            "2025-08-20 13:00:00",
+            "2025-08-22 17:00:00",
+        ),
+        mock_batch(
+            "b2",
+            "2025秋 第二轮选课",
+            "2025-08-25 13:00:00",
+            "2025-08-27 17:00:00",
+        ),
+        mock_batch("b3", "补选", "2025-09-01 09:00:00", "2025-09-02 17:00:00"),
+    ]
+}
+
+#[test]
+fn selects_by_index_code_and_name() {
+    let batches = batches();
+    let now = parse_batch_time("2025-08-01 00:00:00").unwrap();
+    let find = |selector: BatchSelector| find_batch(&batches, &selector, now).unwrap();
+
+    assert_eq!(find(BatchSelector::Index(2)), 2);
+    assert_eq!(find(BatchSelector::Code("b2".to_string())), 1);
+    assert_eq!(find(BatchSelector::Name("第二轮".to_string())), 1);
+    assert_eq!(find(BatchSelector::Name("补选".to_string())), 2);
+}
+
+#[test]
+fn ambiguous_name_lists_matches() {
+    let batches = batches();
+    let now = parse_batch_time("2025-08-01 00:00:00").unwrap();
+
+    let err = find_batch(&batches, &BatchSelector::Name("2025秋".to_string()), now).unwrap_err();
+    match err.kind() {
+        ErrorKind::BatchAmbiguous { matches, .. } => assert_eq!(matches.len(), 2),
+        kind => panic!("unexpected error {kind:?}"),
+    }
+}
+
+#[test]
+fn missing_batch_lists_available_batches() {
+    let batches = batches();
+    let now = parse_batch_time("2025-08-01 00:00:00").unwrap();
+
+    let err = find_batch(&batches, &BatchSelector::Code("b9".to_string()), now).unwrap_err();
+    let message = err.to_string();
+    for batch in &batches {
+        assert!(message.contains(&batch.code));
+        assert!(message.contains(&batch.name));
+    }
+}
+
+#[test]
+fn auto_prefers_open_batch_then_next_one() {
+    let batches = batches();
+    let at = |time: &str| {
+        let now = parse_batch_time(time).unwrap();
+        find_batch(&batches, &BatchSelector::Auto, now)
+    };
+
+    assert_eq!(at("2025-08-21 10:00:00").unwrap(), 0);
+    assert_eq!(at("2025-08-26 10:00:00").unwrap(), 1);
+    // 两轮之间选择下一个开始的批次
+    assert_eq!(at("2025-08-23 10:00:00").unwrap(), 1);
+    assert_eq!(at("2025-08-01 00:00:00").unwrap(), 0);
+
+    let err = at("2025-10-01 00:00:00").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BatchNotFound { .. }));
+}
+
+#[test]
+fn selector_parses_flag_values() {
+    let parse = |s: &str| -> BatchSelector {
+        let Ok(selector) = s.parse();
+        selector
+    };
+    assert_eq!(parse("auto"), BatchSelector::Auto);
+    assert_eq!(parse("1"), BatchSelector::Index(1));
+    assert_eq!(parse("b2"), BatchSelector::Code("b2".to_string()));
+    assert_eq!(parse("code:12"), BatchSelector::Code("12".to_string()));
+    assert_eq!(parse("name:补选"), BatchSelector::Name("补选".to_string()));
+}
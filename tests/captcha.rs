@@ -0,0 +1,89 @@
+//! Tests for the built-in captcha solvers
+
+use funky_lesson_core::app::captcha::{CaptchaSolver, FileDropSolver, StdinSolver};
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::testing::scratch_dir;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[tokio::test]
+async fn file_drop_solver_reads_answer_file() -> Result<()> {
+    let dir = scratch_dir("captcha-answer")?;
+    let solver = FileDropSolver::new(&dir).with_poll_interval(Duration::from_millis(10));
+    let image_path = solver.image_path("uuid-1")?;
+    let answer_path = solver.answer_path("uuid-1")?;
+
+    let answerer = tokio::spawn(async move {
+        while !image_path.exists() {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(std::fs::read(&image_path).unwrap(), b"png");
+        std::fs::write(&answer_path, "ab12\n").unwrap();
+    });
+
+    let answer = solver.solve(b"png".to_vec(), "uuid-1".to_string()).await?;
+    answerer.await.unwrap();
+    assert_eq!(answer, "ab12");
+    assert!(!solver.image_path("uuid-1")?.exists());
+    assert!(!solver.answer_path("uuid-1")?.exists());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_drop_solver_times_out() -> Result<()> {
+    let dir = scratch_dir("captcha-timeout")?;
+    let solver = FileDropSolver::new(&dir)
+        .with_poll_interval(Duration::from_millis(10))
+        .with_timeout(Duration::from_millis(50));
+
+    let err = solver
+        .solve(b"png".to_vec(), "uuid-2".to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CaptchaUnsolved(_)));
+    assert!(!solver.image_path("uuid-2")?.exists());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_drop_solver_rejects_unsafe_uuid() -> Result<()> {
+    let dir = scratch_dir("captcha-unsafe")?.join("drop");
+    let solver = FileDropSolver::new(&dir).with_timeout(Duration::from_millis(50));
+
+    for uuid in ["../escape", "a/b", "a\\b", "..", "", "uuid.png"] {
+        assert!(solver.image_path(uuid).is_err(), "{uuid:?}");
+        assert!(solver.answer_path(uuid).is_err(), "{uuid:?}");
+    }
+    assert!(solver.image_path("0123-abcd-ABCD").is_ok());
+
+    // 服务器返回的 uuid 不能把文件写到目录外
+    let err = solver
+        .solve(b"png".to_vec(), "../escape".to_string())
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::ParseError(_)));
+    assert!(!dir.parent().unwrap().join("escape.png").exists());
+    std::fs::remove_dir_all(dir.parent().unwrap())?;
+    Ok(())
+}
+
+#[test]
+fn stdin_solver_saves_each_account_apart() {
+    let solver = StdinSolver::new("captchas/captcha.png");
+    let first = solver.for_account("2023000001");
+    let second = solver.for_account("../2023000002");
+
+    assert_eq!(
+        first.image_path,
+        PathBuf::from("captchas/captcha-2023000001.png")
+    );
+    assert_eq!(
+        second.image_path,
+        PathBuf::from("captchas/captcha-___2023000002.png")
+    );
+    assert_eq!(second.account.as_deref(), Some("../2023000002"));
+}
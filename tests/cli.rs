@@ -0,0 +1,219 @@
+//! Tests running the command line tool against the mock server
+
+use funky_lesson_core::app::store::SessionStore;
+use funky_lesson_core::error::Result;
+use funky_lesson_core::testing::{
+    MOCK_PASSWORD, MOCK_USERNAME, MockServer, mock_course, scratch_dir,
+};
+use serde_json::{Value, json};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+
+const PASSWORD_ENV: &str = "FUNKY_TEST_PASSWORD";
+
+/// Config logging into `server` as [`MOCK_USERNAME`]
+fn mock_config(server: &MockServer) -> Value {
+    json!({
+        "username": MOCK_USERNAME,
+        "batch": "name:第二轮",
+        "server": server.server_config(),
+    })
+}
+
+/// Run the binary in `dir` against `server`, answering every captcha prompt
+async fn run(server: &MockServer, dir: &Path, args: &[&str]) -> Result<Output> {
+    run_with(dir, mock_config(server), args).await
+}
+
+/// Like [`run`] with the given config file
+async fn run_with(dir: &Path, config: Value, args: &[&str]) -> Result<Output> {
+    std::fs::write(dir.join("config.json"), config.to_string())?;
+
+    let dir = dir.to_path_buf();
+    let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+    let output = tokio::task::spawn_blocking(move || -> std::io::Result<Output> {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_funky_lesson_core"))
+            .current_dir(&dir)
+            .args(["--config", "config.json", "--password-env", PASSWORD_ENV])
+            .args(&args)
+            .env(PASSWORD_ENV, MOCK_PASSWORD)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"1234\n1234\n1234\n")?;
+        child.wait_with_output()
+    })
+    .await
+    .unwrap()?;
+    Ok(output)
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[tokio::test]
+async fn json_output_is_the_whole_stdout() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("cli-json")?;
+
+    let output = run(&server, &dir, &["batches", "--json"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // 验证码提示和登录信息都不在 stdout 中
+    let batches: Value = serde_json::from_str(&stdout(&output)).expect("stdout is not JSON");
+    let batches = batches.as_array().unwrap();
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[1]["code"], "batch-2");
+    assert!(stderr(&output).contains("Login success!"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn garbage_session_file_falls_back_to_login() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("cli-garbage")?;
+    std::fs::write(dir.join("funky_session.json"), "not a session {")?;
+
+    let output = run(&server, &dir, &["login", "--json"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stderr(&output).contains("无法读取保存的会话"));
+    assert_eq!(server.login_count(), 1);
+
+    // 重新登录后会话文件被覆盖为有效内容
+    let saved = SessionStore::new(dir.join("funky_session.json")).load()?;
+    assert_eq!(saved.unwrap().session.batch_id, "batch-2");
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_fails_when_no_target_is_found() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("cli-watch")?;
+
+    let output = run(&server, &dir, &["watch", "--course", "不存在的课"]).await?;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("目标课程均未在收藏或已选课程中找到"));
+    assert!(!stdout(&output).contains("目标课程已全部选上"));
+    assert_eq!(server.enroll_attempts("class-1"), 0);
+
+    // 目标都已选上时才算完成
+    server.set_selected(vec![mock_course("class-1", "高等数学", "张老师")]);
+    let output = run(&server, &dir, &["watch", "--course", "高等数学"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("目标课程已全部选上"));
+
+    let output = run(
+        &server,
+        &dir,
+        &["watch", "--course", "高等数学", "--course", "不存在的课"],
+    )
+    .await?;
+    assert!(!output.status.success());
+    assert!(!stdout(&output).contains("目标课程已全部选上"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn watch_with_accounts_fails_when_a_target_is_not_found() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.add_account("20240002", MOCK_PASSWORD);
+    let dir = scratch_dir("cli-accounts")?;
+    let config = |second: Value| {
+        let mut config = mock_config(&server);
+        config["accounts"] = json!([
+            { "username": MOCK_USERNAME, "password": { "env": PASSWORD_ENV }, "courses": ["高等数学"] },
+            { "username": "20240002", "password": { "env": PASSWORD_ENV }, "courses": second },
+        ]);
+        config
+    };
+
+    // 第二个账号一个目标都没找到，不开始选课
+    let output = run_with(&dir, config(json!(["不存在的课"])), &["watch"]).await?;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("账号 20240002 的目标课程均未在收藏或已选课程中找到"));
+    assert_eq!(server.enroll_attempts("class-1"), 0);
+
+    // 找到的课程都选上后停止，但仍有目标未找到
+    let output = run_with(&dir, config(json!(["大学物理", "不存在的课"])), &["watch"]).await?;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("以下账号失败或有目标未找到: 20240002"));
+    assert_eq!(server.selected().len(), 2);
+
+    let output = run_with(&dir, config(json!(["大学物理"])), &["watch"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn enroll_json_reports_the_whole_summary() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.set_selected(vec![mock_course("class-1", "高等数学", "张老师")]);
+    let dir = scratch_dir("cli-summary")?;
+
+    let output = run(&server, &dir, &["enroll", "--json"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    // 之前已选上的课程也在结果中
+    let report: Value = serde_json::from_str(&stdout(&output)).expect("stdout is not JSON");
+    let courses = report["courses"].as_array().unwrap();
+    assert_eq!(courses.len(), 2);
+    assert_eq!(courses[0]["class_id"], "class-1");
+    assert_eq!(courses[0]["outcome"]["kind"], "already_selected");
+    assert_eq!(courses[1]["class_id"], "class-2");
+    assert_eq!(courses[1]["outcome"]["kind"], "success");
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn batches_lists_every_batch_when_none_can_be_selected() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("cli-no-batch")?;
+    // 模拟服务器的批次都已结束，自动选择找不到批次
+    let mut config = mock_config(&server);
+    config.as_object_mut().unwrap().remove("batch");
+
+    let output = run_with(&dir, config, &["batches", "--json"]).await?;
+    assert!(output.status.success(), "{}", stderr(&output));
+    let batches: Value = serde_json::from_str(&stdout(&output)).expect("stdout is not JSON");
+    assert_eq!(batches.as_array().unwrap().len(), 2);
+    assert!(stderr(&output).contains("请用 --batch 选择"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_gives_up_on_a_permanent_error() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("cli-no-aes-key")?;
+    // 该页面不含 AES 密钥，重试也不会成功
+    let mut config = mock_config(&server);
+    config["server"]["endpoints"]["index"] = json!("/xsxk/elective/grablessons");
+
+    let output = tokio::time::timeout(
+        std::time::Duration::from_secs(30),
+        run_with(&dir, config, &["login"]),
+    )
+    .await
+    .expect("login kept retrying")?;
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("AesKeyNotFound"));
+    assert!(!stderr(&output).contains("重试中"));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,81 @@
+//! Tests for server clock estimation
+
+use chrono::{TimeDelta, Utc};
+use funky_lesson_core::client::clock::{ClockSample, ClockSync};
+use funky_lesson_core::client::request::NoWasmClient;
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::interface::HttpClient;
+use funky_lesson_core::testing::MockServer;
+use std::time::Duration;
+
+#[test]
+fn min_delay_sample_wins() {
+    let samples = [
+        ClockSample {
+            offset: TimeDelta::milliseconds(900),
+            rtt: Duration::from_millis(300),
+        },
+        ClockSample {
+            offset: TimeDelta::milliseconds(120),
+            rtt: Duration::from_millis(20),
+        },
+        ClockSample {
+            offset: TimeDelta::milliseconds(-400),
+            rtt: Duration::from_millis(80),
+        },
+    ];
+
+    let sync = ClockSync::from_samples(&samples).unwrap();
+    assert_eq!(sync.offset(), TimeDelta::milliseconds(120));
+    assert_eq!(sync.rtt(), Duration::from_millis(20));
+    assert_eq!(sync.samples(), 3);
+    assert!(ClockSync::from_samples(&[]).is_none());
+}
+
+#[test]
+fn sample_centers_second_resolution_date() {
+    let sent = Utc::now();
+    let date = sent + TimeDelta::seconds(10);
+    let sample = ClockSample::new(sent, Duration::from_millis(100), date);
+
+    // 10s skew + 500ms for the truncated second - 50ms half round trip
+    assert_eq!(sample.offset, TimeDelta::milliseconds(10_450));
+}
+
+#[tokio::test]
+async fn measures_injected_skew_from_mock_server() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.set_clock_skew(TimeDelta::seconds(-30));
+    let client = NoWasmClient::with_config(server.server_config()).await?;
+
+    let sync = ClockSync::measure(&client, 3).await?;
+
+    // `Date` only has second resolution, so allow the truncation error
+    let error = (sync.offset() - TimeDelta::seconds(-30)).abs();
+    assert!(
+        error <= TimeDelta::milliseconds(600),
+        "offset {:?}",
+        sync.offset()
+    );
+    let server_now = sync.server_now();
+    assert!((Utc::now() - server_now - TimeDelta::seconds(30)).abs() <= TimeDelta::seconds(1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn measure_skips_failed_samples() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.set_clock_skew(TimeDelta::seconds(-30));
+    let client = NoWasmClient::with_config(server.server_config()).await?;
+
+    server.break_dates(2);
+    let sync = ClockSync::measure(&client, 3).await?;
+    assert_eq!(sync.samples(), 1);
+    assert!((sync.offset() - TimeDelta::seconds(-30)).abs() <= TimeDelta::milliseconds(600));
+
+    // 所有样本都失败时才报错
+    server.break_dates(3);
+    let error = ClockSync::measure(&client, 3).await.unwrap_err();
+    assert!(matches!(error.kind(), ErrorKind::ParseError(_)));
+    Ok(())
+}
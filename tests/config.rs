@@ -0,0 +1,261 @@
+//! Tests for the command line configuration file
+
+use funky_lesson_core::app::config::{AppConfig, PasswordSource};
+use funky_lesson_core::client::rate_limit::Endpoint;
+use funky_lesson_core::model::structs::{BatchSelector, CourseFilter, CourseInfo, CourseTarget};
+use funky_lesson_core::testing::{mock_course, scratch_dir};
+use std::time::Duration;
+
+#[test]
+fn toml_config_parses_every_section() {
+    let config = AppConfig::from_toml(
+        r#"
+        username = "2023000000"
+        password = { env = "FUNKY_TEST_PASSWORD" }
+        batch = { code = "batch-2" }
+        courses = ["class-1", "线性代数"]
+        loop = true
+
+        [enroll]
+        workers = 2
+        interval_ms = 200
+        try_if_capacity_full = false
+
+        [rate_limit]
+        addxk_rps = 5.0
+
+        [schedule]
+        enabled = true
+        lead_ms = 300
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.username.as_deref(), Some("2023000000"));
+    assert_eq!(
+        config.password,
+        Some(PasswordSource::Env {
+            env: "FUNKY_TEST_PASSWORD".to_string()
+        })
+    );
+    assert_eq!(
+        config.batch,
+        Some(BatchSelector::Code("batch-2".to_string()))
+    );
+    assert_eq!(config.loop_mode, Some(true));
+
+    let enroll = config.enroll_config();
+    assert_eq!(enroll.workers, 2);
+    assert_eq!(enroll.interval, Duration::from_millis(200));
+    assert!(!enroll.try_if_capacity_full);
+    // 未设置的字段保持默认值
+    assert_eq!(enroll.backoff.initial, Duration::from_millis(500));
+
+    let schedule = config.schedule.schedule_config().unwrap();
+    assert_eq!(schedule.lead_time, Duration::from_millis(300));
+}
+
+#[test]
+fn json_config_accepts_index_and_plain_password() {
+    let config = AppConfig::from_json(r#"{"username": "u", "password": "p", "batch": 1}"#).unwrap();
+
+    assert_eq!(config.batch, Some(BatchSelector::Index(1)));
+    assert_eq!(config.password.unwrap().resolve().unwrap(), "p");
+    assert!(config.schedule.schedule_config().is_none());
+}
+
+#[test]
+fn batch_accepts_strings_like_the_flag() {
+    let batch = |value: &str| {
+        AppConfig::from_toml(&format!("batch = {value}"))
+            .unwrap()
+            .batch
+            .unwrap()
+    };
+    assert_eq!(batch("\"auto\""), BatchSelector::Auto);
+    assert_eq!(
+        batch("\"batch-1\""),
+        BatchSelector::Code("batch-1".to_string())
+    );
+    assert_eq!(
+        batch("\"name:第二轮\""),
+        BatchSelector::Name("第二轮".to_string())
+    );
+    assert_eq!(
+        batch("{ name = \"第二轮\" }"),
+        BatchSelector::Name("第二轮".to_string())
+    );
+}
+
+#[test]
+fn unknown_fields_are_rejected() {
+    assert!(AppConfig::from_toml("usrname = \"typo\"").is_err());
+    assert!(AppConfig::from_toml("[enroll]\nworker = 2").is_err());
+}
+
+#[test]
+fn password_sources_resolve_without_leaking() {
+    let dir = scratch_dir("password").unwrap();
+    let path = dir.join("password.txt");
+    std::fs::write(&path, "secret\n").unwrap();
+    let file = PasswordSource::File { file: path.clone() };
+    assert_eq!(file.resolve().unwrap(), "secret");
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let missing = PasswordSource::Env {
+        env: "FUNKY_TEST_UNSET_PASSWORD".to_string(),
+    };
+    assert!(missing.resolve().is_err());
+
+    let plain = PasswordSource::Plain("secret".to_string());
+    assert!(!format!("{plain:?}").contains("secret"));
+}
+
+#[test]
+fn target_courses_resolve_against_favorites() {
+    let favorites = vec![
+        mock_course("class-1", "高等数学", "张老师"),
+        mock_course("class-2", "大学英语", "李老师"),
+        mock_course("class-3", "大学英语", "王老师"),
+    ];
+    let selected = vec![mock_course("class-1", "高等数学", "张老师")];
+
+    let all = AppConfig::default().target_courses(&favorites, &selected);
+    assert_eq!(ids(&all.courses), ["class-2", "class-3"]);
+    assert_eq!(ids(&all.already_selected), ["class-1"]);
+    assert!(all.unresolved.is_empty());
+
+    let config = AppConfig::from_toml(
+        r#"courses = ["李老师", "高等数学", { name = "大学英语", teacher = "王老师" }, "线性代数", "teacher:赵老师"]"#,
+    )
+    .unwrap();
+    let targets = config.target_courses(&favorites, &selected);
+    assert_eq!(ids(&targets.courses), ["class-2", "class-3"]);
+    assert_eq!(ids(&targets.already_selected), ["class-1"]);
+    let unresolved: Vec<String> = targets.unresolved.iter().map(|t| t.to_string()).collect();
+    assert_eq!(unresolved, ["线性代数", "teacher:赵老师"]);
+}
+
+#[test]
+fn course_targets_parse_like_the_flag() {
+    let parse = |s: &str| s.parse::<CourseTarget>().unwrap();
+    let course = mock_course("class-1", "高等数学", "张老师");
+
+    assert_eq!(parse("高等数学"), CourseTarget::Any("高等数学".to_string()));
+    assert!(parse("张老师").matches(&course));
+    assert!(parse("id:class-1").matches(&course));
+    assert!(!parse("name:class-1").matches(&course));
+    assert!(parse("teacher: 张老师").matches(&course));
+    assert_eq!(
+        parse("name:高等数学"),
+        CourseTarget::Match(CourseFilter {
+            name: Some("高等数学".to_string()),
+            ..CourseFilter::default()
+        })
+    );
+    // 空的筛选条件不匹配任何课程
+    assert!(!CourseTarget::Match(CourseFilter::default()).matches(&course));
+    assert!(AppConfig::from_toml(r#"courses = [{ title = "x" }]"#).is_err());
+}
+
+fn ids(courses: &[CourseInfo]) -> Vec<&str> {
+    courses.iter().map(|c| c.JXBID.as_str()).collect()
+}
+
+#[test]
+fn accounts_are_read_from_array_tables() {
+    let config = AppConfig::from_toml(
+        r#"
+        batch = 1
+
+        [[accounts]]
+        username = "2023000001"
+        password = { file = "alice.txt" }
+        courses = ["class-1"]
+
+        [[accounts]]
+        username = "2023000002"
+        batch = "name:第二轮"
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(config.accounts.len(), 2);
+    let [alice, bob] = &config.accounts[..] else {
+        unreachable!()
+    };
+    assert_eq!(alice.username, "2023000001");
+    assert!(matches!(alice.password, Some(PasswordSource::File { .. })));
+    assert_eq!(alice.courses, vec![CourseTarget::from("class-1")]);
+    assert_eq!(alice.batch, None);
+    // 没有密码时从保险库读取
+    assert_eq!(bob.password, None);
+    assert_eq!(bob.batch, Some(BatchSelector::Name("第二轮".to_string())));
+    assert!(AppConfig::from_toml("[[accounts]]\ncourses = []").is_err());
+}
+
+#[test]
+fn groups_resolve_in_priority_order_until_one_is_selected() {
+    let favorites = vec![
+        mock_course("class-1", "体育", "张老师"),
+        mock_course("class-2", "体育", "李老师"),
+        mock_course("class-3", "高等数学", "王老师"),
+        mock_course("class-4", "大学英语", "赵老师"),
+        mock_course("class-5", "大学英语", "钱老师"),
+    ];
+    let config = AppConfig::from_toml(
+        r#"
+        groups = [
+            [{ name = "体育", teacher = "李老师" }, "张老师"],
+            ["class-4", "class-5", "线性代数"],
+        ]
+        "#,
+    )
+    .unwrap();
+
+    let open = config.target_courses(&favorites, &[]);
+    // 只列出分组时只选分组内的课程
+    assert_eq!(
+        ids(&open.courses),
+        ["class-1", "class-2", "class-4", "class-5"]
+    );
+    let groups: Vec<Vec<&str>> = open
+        .groups
+        .iter()
+        .map(|g| g.class_ids.iter().map(String::as_str).collect())
+        .collect();
+    assert_eq!(
+        groups,
+        [vec!["class-2", "class-1"], vec!["class-4", "class-5"]]
+    );
+    assert_eq!(open.unresolved, [CourseTarget::from("线性代数")]);
+
+    // 组内已有课程选上，其余备选不再选
+    let selected = vec![mock_course("class-5", "大学英语", "钱老师")];
+    let resolved = config.target_courses(&favorites, &selected);
+    assert_eq!(ids(&resolved.courses), ["class-1", "class-2"]);
+    assert_eq!(resolved.groups.len(), 1);
+    assert_eq!(ids(&resolved.already_selected), ["class-5"]);
+}
+
+#[tokio::test]
+async fn enroll_max_rps_limits_the_shared_client_limiter() {
+    let config = AppConfig::from_toml(
+        r#"
+        [enroll]
+        max_rps = 20.0
+        "#,
+    )
+    .unwrap();
+
+    // 限制加在所有账号共用的限流器上，而不是每次运行各自限流
+    assert!(config.enroll_config().max_rps.is_none());
+    let limiter = config.rate_limiter();
+    for _ in 0..3 {
+        limiter.acquire(Endpoint::AddCourse).await;
+    }
+    assert_eq!(
+        limiter.metrics().endpoints[&Endpoint::AddCourse].throttled,
+        2
+    );
+}
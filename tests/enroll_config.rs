@@ -0,0 +1,29 @@
+//! Tests for enrollment pacing configuration
+
+use funky_lesson_core::app::engine::{Backoff, EnrollConfig, Strategy};
+use std::time::Duration;
+
+#[test]
+fn backoff_grows_exponentially_up_to_max() {
+    let backoff = Backoff {
+        initial: Duration::from_millis(100),
+        max: Duration::from_millis(1000),
+        multiplier: 2.0,
+    };
+
+    assert_eq!(backoff.delay(1), Duration::from_millis(100));
+    assert_eq!(backoff.delay(2), Duration::from_millis(200));
+    assert_eq!(backoff.delay(4), Duration::from_millis(800));
+    assert_eq!(backoff.delay(5), Duration::from_millis(1000));
+    assert_eq!(backoff.delay(u32::MAX), Duration::from_millis(1000));
+}
+
+#[test]
+fn default_config_is_unthrottled_single_pass() {
+    let config = EnrollConfig::default();
+
+    assert_eq!(config.strategy, Strategy::SinglePass);
+    assert_eq!(config.workers, 4);
+    assert!(config.interval.is_zero());
+    assert!(config.max_rps.is_none());
+}
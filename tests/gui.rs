@@ -0,0 +1,124 @@
+//! Tests for the GUI enrollment adapter, run with `--features gui`
+#![cfg(feature = "gui")]
+
+use funky_lesson_core::CancellationToken;
+use funky_lesson_core::TokioMutex;
+use funky_lesson_core::app::captcha::ChannelSolver;
+use funky_lesson_core::app::engine::EnrollConfig;
+use funky_lesson_core::app::gui::enroll_courses_with_session;
+use funky_lesson_core::error::Result;
+use funky_lesson_core::model::structs::{CompletionReason, EnrollOutcome, EnrollmentStatus};
+use funky_lesson_core::testing::{MockServer, mock_course};
+use std::sync::Arc;
+
+#[tokio::test]
+async fn gui_status_tracks_each_run_until_it_ends() -> Result<()> {
+    let server = MockServer::start().await?;
+    let (solver, mut captchas) = ChannelSolver::new();
+    let session = server.session_manager(solver).await?;
+    let login = tokio::spawn({
+        let session = Arc::clone(&session);
+        async move { session.get_courses().await }
+    });
+    captchas.recv().await.unwrap().respond("1234");
+    let (_, favorites) = login.await.unwrap()?;
+
+    let status = Arc::new(TokioMutex::new(EnrollmentStatus::default()));
+    let config = EnrollConfig::default();
+    enroll_courses_with_session(
+        &session,
+        &favorites,
+        &config,
+        Arc::clone(&status),
+        CancellationToken::new(),
+    )
+    .await?;
+    {
+        let status = status.lock().await;
+        assert!(!status.is_running);
+        assert_eq!(status.completion, Some(CompletionReason::AllFinished));
+        assert_eq!(status.course_outcomes.len(), favorites.len());
+        assert_eq!(status.course_statuses.len(), favorites.len());
+        assert!(
+            status
+                .course_outcomes
+                .iter()
+                .all(|c| c.finished && c.outcome == Some(EnrollOutcome::Success))
+        );
+        assert!(status.total_requests >= 2);
+    }
+
+    // 第二次运行在重新登录时暂停，此时状态应已清空
+    server.expire_tokens();
+    let run = tokio::spawn({
+        let session = Arc::clone(&session);
+        let favorites = favorites.clone();
+        let status = Arc::clone(&status);
+        async move {
+            enroll_courses_with_session(
+                &session,
+                &favorites,
+                &config,
+                status,
+                CancellationToken::new(),
+            )
+            .await
+        }
+    });
+    let captcha = captchas.recv().await.unwrap();
+    {
+        let status = status.lock().await;
+        assert!(status.is_running);
+        assert_eq!(status.completion, None);
+        assert_eq!(status.total_requests, 0);
+        assert_eq!(
+            status.course_statuses,
+            ["[高等数学]等待中", "[大学物理]等待中"]
+        );
+        assert!(status.course_outcomes.iter().all(|c| c.outcome.is_none()));
+    }
+    captcha.respond("1234");
+    run.await.unwrap()?;
+
+    let status = status.lock().await;
+    assert!(!status.is_running);
+    assert_eq!(status.completion, Some(CompletionReason::AllFinished));
+    assert!(
+        status
+            .course_outcomes
+            .iter()
+            .all(|c| c.outcome == Some(EnrollOutcome::AlreadySelected))
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn gui_status_reports_a_cancelled_run_as_stopped() -> Result<()> {
+    let server = MockServer::start().await?;
+    let (solver, _captchas) = ChannelSolver::new();
+    let session = server.session_manager(solver).await?;
+    let favorites = vec![
+        mock_course("class-1", "高等数学", "张老师"),
+        mock_course("class-2", "大学物理", "李老师"),
+    ];
+
+    let status = Arc::new(TokioMutex::new(EnrollmentStatus::default()));
+    let cancel = CancellationToken::new();
+    cancel.cancel();
+    enroll_courses_with_session(
+        &session,
+        &favorites,
+        &EnrollConfig::default(),
+        Arc::clone(&status),
+        cancel,
+    )
+    .await?;
+
+    let status = status.lock().await;
+    assert!(!status.is_running);
+    assert_eq!(status.completion, Some(CompletionReason::Stopped));
+    assert_eq!(status.course_outcomes.len(), favorites.len());
+    assert!(status.course_outcomes.iter().all(|c| !c.finished));
+    assert_eq!(status.total_requests, 0);
+    Ok(())
+}
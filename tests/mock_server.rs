@@ -0,0 +1,813 @@
+//! Integration tests driving the app layer against the in-process mock server
+
+use funky_lesson_core::CancellationToken;
+use funky_lesson_core::app::captcha::ChannelSolver;
+use funky_lesson_core::app::engine::{
+    Backoff, CourseGroup, EnrollConfig, EnrollEvent, EnrollmentEngine, Strategy,
+};
+use funky_lesson_core::app::session::{SessionEvent, SessionManager};
+use funky_lesson_core::app::{
+    enroll_courses, enroll_courses_with_session, get_courses, login_with_captcha, set_batch,
+};
+use funky_lesson_core::client::rate_limit::{Endpoint, RateLimit, RateLimiter};
+use funky_lesson_core::client::request::NoWasmClient;
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::interface::{HttpClient, RequestApi};
+use funky_lesson_core::model::dtos::CourseSelectParams;
+use funky_lesson_core::model::structs::{BatchInfo, BatchSelector, EnrollOutcome};
+use std::sync::Arc;
+use std::time::Duration;
+
+use funky_lesson_core::testing::{
+    MOCK_AES_KEY, MOCK_CAPTCHA_UUID, MOCK_PASSWORD, MOCK_USERNAME, MockReply, MockServer,
+    MockSolver, mock_course,
+};
+
+async fn setup() -> Result<(MockServer, NoWasmClient)> {
+    let server = MockServer::start().await?;
+    let client = NoWasmClient::with_config(server.server_config()).await?;
+    Ok((server, client))
+}
+
+async fn login(client: &NoWasmClient) -> Result<(String, Vec<BatchInfo>)> {
+    let captcha = client.get_captcha().await?;
+    login_with_captcha(client, MOCK_USERNAME, MOCK_PASSWORD, "1234", &captcha.uuid).await
+}
+
+#[tokio::test]
+async fn serves_aes_key_and_captcha() -> Result<()> {
+    let (_server, client) = setup().await?;
+
+    assert_eq!(client.get_aes_key().await?, MOCK_AES_KEY.as_bytes());
+
+    let captcha = client.get_captcha().await?;
+    assert_eq!(captcha.uuid, MOCK_CAPTCHA_UUID);
+    assert!(captcha.captcha.starts_with("data:image/png;base64,"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_returns_token_and_batches() -> Result<()> {
+    let (server, client) = setup().await?;
+
+    let (token, batches) = login(&client).await?;
+    assert!(!token.is_empty());
+    assert_eq!(batches.len(), 2);
+    assert_eq!(batches[0].code, "batch-1");
+    assert_eq!(server.login_count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_rejects_wrong_password() -> Result<()> {
+    let (server, client) = setup().await?;
+
+    let err = login_with_captcha(&client, MOCK_USERNAME, "wrong", "1234", MOCK_CAPTCHA_UUID)
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::LoginRejected { .. }));
+    assert_eq!(server.login_count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_rejects_wrong_captcha() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_captcha("abcd");
+
+    let err = login(&client).await.unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CaptchaWrong));
+
+    let (token, _) = login_with_captcha(
+        &client,
+        MOCK_USERNAME,
+        MOCK_PASSWORD,
+        "abcd",
+        MOCK_CAPTCHA_UUID,
+    )
+    .await?;
+    assert!(!token.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_batch_by_index() -> Result<()> {
+    let (server, client) = setup().await?;
+    let (token, batches) = login(&client).await?;
+
+    let batch_id = set_batch(&client, &token, &batches, 1).await?;
+    assert_eq!(batch_id, "batch-2");
+    assert_eq!(server.current_batch().as_deref(), Some("batch-2"));
+
+    let err = set_batch(&client, &token, &batches, 2).await.unwrap_err();
+    match err.kind() {
+        ErrorKind::BatchNotFound {
+            selector,
+            available,
+        } => {
+            assert_eq!(*selector, BatchSelector::Index(2));
+            assert_eq!(available.len(), 2);
+        }
+        kind => panic!("unexpected error {kind:?}"),
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn set_batch_by_code_or_name() -> Result<()> {
+    let (server, client) = setup().await?;
+    let (token, batches) = login(&client).await?;
+
+    let by_code = BatchSelector::Code("batch-2".to_string());
+    assert_eq!(
+        set_batch(&client, &token, &batches, by_code).await?,
+        "batch-2"
+    );
+
+    let by_name = BatchSelector::Name("第一轮".to_string());
+    assert_eq!(
+        set_batch(&client, &token, &batches, by_name).await?,
+        "batch-1"
+    );
+    assert_eq!(server.current_batch().as_deref(), Some("batch-1"));
+
+    let missing = BatchSelector::Code("batch-9".to_string());
+    let err = set_batch(&client, &token, &batches, missing)
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BatchNotFound { .. }));
+    assert!(err.to_string().contains("batch-1 第一轮选课"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_courses_lists_selected_and_favorites() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_selected(vec![mock_course("class-9", "线性代数", "王老师")]);
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+
+    let (selected, favorites) = get_courses(&client, &token, &batch_id).await?;
+    assert_eq!(selected.len(), 1);
+    assert_eq!(selected[0].JXBID, "class-9");
+    assert_eq!(favorites.len(), 2);
+    assert_eq!(favorites[0].secret_val.as_deref(), Some("secret-class-1"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn get_courses_rejects_expired_token() -> Result<()> {
+    let (server, client) = setup().await?;
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+
+    server.expire_tokens();
+    let err = get_courses(&client, &token, &batch_id).await.unwrap_err();
+    assert!(err.is_token_expired());
+    Ok(())
+}
+
+#[tokio::test]
+async fn enroll_courses_retries_until_selected() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll(
+        "class-1",
+        [
+            MockReply::not_started(),
+            MockReply::capacity_full(),
+            MockReply::success(),
+        ],
+    );
+    server.set_selected(vec![mock_course("class-2", "大学物理", "李老师")]);
+
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+    let (_, favorites) = get_courses(&client, &token, &batch_id).await?;
+
+    enroll_courses(&client, &token, &batch_id, &favorites, true).await?;
+
+    // Workers race on the same course, so only lower bounds are exact
+    assert!(server.enroll_attempts("class-1") >= 3);
+    assert!(server.enroll_attempts("class-2") >= 1);
+    let selected = server.selected();
+    assert!(selected.iter().any(|c| c.JXBID == "class-1"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn enroll_courses_gives_up_on_full_course() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_favorites(vec![mock_course("class-1", "高等数学", "张老师")]);
+    server.script_enroll(
+        "class-1",
+        std::iter::repeat_n(MockReply::capacity_full(), 64),
+    );
+
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+    let (_, favorites) = get_courses(&client, &token, &batch_id).await?;
+
+    enroll_courses(&client, &token, &batch_id, &favorites, false).await?;
+
+    assert!(server.enroll_attempts("class-1") >= 1);
+    assert!(server.selected().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn business_error_with_error_status_keeps_its_envelope() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_error_status(500);
+    server.script_enroll("class-1", [MockReply::capacity_full()]);
+
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+    let params = CourseSelectParams {
+        token,
+        batch_id,
+        class_type: "XGKC".to_string(),
+        class_id: "class-1".to_string(),
+        secret_val: "secret-class-1".to_string(),
+    };
+
+    // 500 状态码下的业务错误不是传输失败
+    let response = client.select_course(params).await?;
+    assert_eq!(response.code, 500);
+    assert_eq!(response.msg, MockReply::capacity_full().msg);
+    Ok(())
+}
+
+#[tokio::test]
+async fn enroll_courses_stops_when_unauthorized() -> Result<()> {
+    let (server, client) = setup().await?;
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+    let (_, favorites) = get_courses(&client, &token, &batch_id).await?;
+
+    server.expire_tokens();
+    enroll_courses(&client, &token, &batch_id, &favorites, true).await?;
+
+    assert!(server.selected().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_relogs_in_when_token_expires_mid_run() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_favorites(vec![mock_course("class-1", "高等数学", "张老师")]);
+    server.script_enroll(
+        "class-1",
+        [MockReply::not_started(), MockReply::capacity_full()],
+    );
+    let solver = MockSolver::default();
+    let session = MockServer::session_manager_with(client, solver.clone());
+
+    let (_, favorites) = session.get_courses().await?;
+    assert_eq!(server.login_count(), 1);
+    assert_eq!(server.current_batch().as_deref(), Some("batch-1"));
+
+    let mut events = session.subscribe();
+    server.expire_tokens();
+    enroll_courses_with_session(
+        &session,
+        &favorites,
+        &EnrollConfig::default(),
+        &CancellationToken::new(),
+    )
+    .await?;
+
+    // Concurrent 401s are coalesced into a single re-login
+    assert_eq!(events.try_recv().ok(), Some(SessionEvent::Relogin));
+    assert!(events.try_recv().is_err());
+    assert_eq!(server.login_count(), 2);
+    assert_eq!(solver.calls(), 2);
+    assert!(session.current().await?.token.ends_with("-2"));
+    assert!(server.selected().iter().any(|c| c.JXBID == "class-1"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn session_retries_wrong_captcha_before_giving_up() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_captcha("abcd");
+    let solver = MockSolver::default();
+    let session = MockServer::session_manager_with(client, solver.clone());
+    let mut events = session.subscribe();
+
+    let err = session.current().await.unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CaptchaWrong));
+    assert_eq!(solver.calls(), 3);
+    for attempt in 1..=2 {
+        assert_eq!(
+            events.try_recv().ok(),
+            Some(SessionEvent::CaptchaRejected { attempt })
+        );
+    }
+    assert!(events.try_recv().is_err());
+    assert_eq!(server.login_count(), 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn token_only_session_cannot_relogin() -> Result<()> {
+    let (server, client) = setup().await?;
+    let (token, batches) = login(&client).await?;
+    let batch_id = set_batch(&client, &token, &batches, 0).await?;
+    let session = SessionManager::with_token(client, &token, &batch_id);
+
+    server.expire_tokens();
+    let err = session.get_courses().await.unwrap_err();
+    assert!(err.is_token_expired());
+    assert_eq!(server.login_count(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_asks_channel_solver_for_captcha() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_captcha("abcd");
+    let (solver, mut requests) = ChannelSolver::new();
+
+    let ui = tokio::spawn(async move {
+        let request = requests.recv().await.unwrap();
+        assert_eq!(request.uuid, MOCK_CAPTCHA_UUID);
+        assert!(request.image.starts_with(b"\x89PNG"));
+        request.respond("abcd");
+    });
+
+    let (token, batches) =
+        funky_lesson_core::app::login(&client, MOCK_USERNAME, MOCK_PASSWORD, &solver).await?;
+    ui.await.unwrap();
+    assert!(!token.is_empty());
+    assert_eq!(batches.len(), 2);
+    Ok(())
+}
+
+#[tokio::test]
+async fn login_fails_when_captcha_receiver_is_gone() -> Result<()> {
+    let (_server, client) = setup().await?;
+    let (solver, requests) = ChannelSolver::new();
+    drop(requests);
+
+    let err = funky_lesson_core::app::login(&client, MOCK_USERNAME, MOCK_PASSWORD, &solver)
+        .await
+        .unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CaptchaUnsolved(_)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_streams_events_until_finished() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll("class-1", [MockReply::not_started()]);
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let engine = EnrollmentEngine::new(Arc::clone(&session), EnrollConfig::default());
+    let mut events = engine.subscribe();
+    let report = engine.run(&favorites).await?;
+
+    let mut finished = Vec::new();
+    let mut last = None;
+    while let Ok(event) = events.try_recv() {
+        if let EnrollEvent::CourseFinished { class_id, .. } = &event {
+            finished.push(class_id.clone());
+        }
+        last = Some(event);
+    }
+    finished.sort();
+    assert_eq!(finished, ["class-1", "class-2"]);
+    assert!(matches!(last, Some(EnrollEvent::Finished(_))));
+
+    assert_eq!(report.courses.len(), 2);
+    assert!(
+        report
+            .courses
+            .iter()
+            .all(|c| c.outcome.as_ref().is_some_and(EnrollOutcome::is_selected))
+    );
+    assert!(report.total_requests >= 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn continuous_engine_retries_open_courses_until_stopped() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll(
+        "class-1",
+        std::iter::repeat_n(MockReply::not_started(), 10_000),
+    );
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let cancel = CancellationToken::new();
+    let config = EnrollConfig {
+        strategy: Strategy::Continuous,
+        workers: 2,
+        ..EnrollConfig::default()
+    };
+    let engine = EnrollmentEngine::new(session, config).with_cancellation(cancel.clone());
+
+    let stopper = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(700)).await;
+        cancel.cancel();
+    });
+    let report = engine.run(&favorites).await?;
+    stopper.await.unwrap();
+
+    // 未开始的课程一直重试，已选上的课程不再请求
+    assert!(server.enroll_attempts("class-1") >= 2);
+    assert!(server.enroll_attempts("class-2") <= 2);
+    assert_eq!(server.selected().len(), 1);
+    assert!(report.courses[1].finished);
+    assert!(!report.is_complete());
+    Ok(())
+}
+
+#[tokio::test]
+async fn continuous_engine_finishes_once_every_course_is_final() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll("class-1", [MockReply::not_started()]);
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let config = EnrollConfig {
+        strategy: Strategy::Continuous,
+        workers: 2,
+        ..EnrollConfig::default()
+    };
+    // 没有人取消，选上全部课程后自行结束
+    let report = tokio::time::timeout(
+        Duration::from_secs(5),
+        EnrollmentEngine::new(session, config).run(&favorites),
+    )
+    .await
+    .expect("continuous run did not finish")?;
+
+    assert!(report.is_complete());
+    assert_eq!(server.selected().len(), 2);
+    assert!(server.enroll_attempts("class-1") <= 3);
+    Ok(())
+}
+
+#[tokio::test]
+async fn cancelling_interrupts_pause_between_requests() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll("class-1", std::iter::repeat_n(MockReply::not_started(), 10));
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let cancel = CancellationToken::new();
+    let config = EnrollConfig {
+        strategy: Strategy::Continuous,
+        workers: 1,
+        interval: Duration::from_secs(30),
+        ..EnrollConfig::default()
+    };
+    let engine = EnrollmentEngine::new(session, config).with_cancellation(cancel.clone());
+    let stopper = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        cancel.cancel();
+    });
+    // 取消后不必等完 30 秒的间隔
+    let report = tokio::time::timeout(Duration::from_secs(5), engine.run(&favorites))
+        .await
+        .expect("cancelled run kept sleeping")?;
+    stopper.await.unwrap();
+
+    assert!(server.enroll_attempts("class-1") <= 1);
+    assert!(!report.is_complete());
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_respects_max_rps_across_workers() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_favorites(vec![mock_course("class-1", "高等数学", "张老师")]);
+    server.script_enroll("class-1", std::iter::repeat_n(MockReply::not_started(), 9));
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let config = EnrollConfig {
+        workers: 4,
+        max_rps: Some(20.0),
+        ..EnrollConfig::default()
+    };
+    let engine = EnrollmentEngine::new(session, config);
+    let started = std::time::Instant::now();
+    let report = engine.run(&favorites).await?;
+
+    // 10 requests at most 20 per second take at least 9 spacings of 50ms
+    assert!(report.total_requests >= 10);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(450));
+    assert_eq!(server.selected().len(), 1);
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_workers_share_the_addxk_rate_limit() -> Result<()> {
+    let server = MockServer::start().await?;
+    let limiter =
+        RateLimiter::new(None).with_endpoint(Endpoint::AddCourse, RateLimit::per_second(20.0));
+    let client = NoWasmClient::with_config(server.server_config())
+        .await?
+        .with_rate_limiter(Arc::new(limiter));
+    server.set_favorites(vec![mock_course("class-1", "高等数学", "张老师")]);
+    server.script_enroll("class-1", std::iter::repeat_n(MockReply::not_started(), 9));
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let config = EnrollConfig {
+        workers: 4,
+        ..EnrollConfig::default()
+    };
+    let engine = EnrollmentEngine::new(session, config);
+    let started = std::time::Instant::now();
+    let report = engine.run(&favorites).await?;
+
+    // 10 requests at most 20 per second take at least 9 spacings of 50ms
+    assert!(report.total_requests >= 10);
+    assert!(started.elapsed() >= std::time::Duration::from_millis(450));
+    assert_eq!(server.selected().len(), 1);
+    Ok(())
+}
+
+fn grouped_favorites() -> Vec<funky_lesson_core::model::structs::CourseInfo> {
+    vec![
+        mock_course("class-1", "高等数学A", "张老师"),
+        mock_course("class-2", "高等数学B", "王老师"),
+        mock_course("class-3", "大学物理", "李老师"),
+    ]
+}
+
+fn group_config() -> EnrollConfig {
+    EnrollConfig {
+        groups: vec![CourseGroup {
+            class_ids: vec!["class-1".to_string(), "class-2".to_string()],
+        }],
+        ..EnrollConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn engine_group_falls_back_to_next_alternative() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_favorites(grouped_favorites());
+    server.script_enroll(
+        "class-1",
+        std::iter::repeat_n(MockReply::capacity_full(), 3),
+    );
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let engine = EnrollmentEngine::new(session, group_config());
+    let mut events = engine.subscribe();
+    let report = engine.run(&favorites).await?;
+
+    // 第一志愿已满，改选第二志愿后不再请求第一志愿
+    assert_eq!(server.enroll_attempts("class-1"), 1);
+    let mut selected: Vec<String> = server.selected().into_iter().map(|c| c.JXBID).collect();
+    selected.sort();
+    assert_eq!(selected, ["class-2", "class-3"]);
+    assert_eq!(
+        report.courses[0].outcome,
+        Some(EnrollOutcome::AlternativeSelected {
+            class_id: "class-2".to_string()
+        })
+    );
+
+    let mut skipped = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EnrollEvent::CourseFinished {
+            class_id,
+            outcome: EnrollOutcome::AlternativeSelected { .. },
+            ..
+        } = event
+        {
+            skipped.push(class_id);
+        }
+    }
+    assert_eq!(skipped, ["class-1"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_group_skips_alternatives_once_first_choice_is_in() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.set_favorites(grouped_favorites());
+    server.script_enroll("class-1", [MockReply::already_selected()]);
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let config = EnrollConfig {
+        strategy: Strategy::Continuous,
+        ..group_config()
+    };
+    let cancel = CancellationToken::new();
+    let engine = EnrollmentEngine::new(session, config).with_cancellation(cancel.clone());
+    let stopper = tokio::spawn(async move {
+        tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+        cancel.cancel();
+    });
+    let report = engine.run(&favorites).await?;
+    stopper.await.unwrap();
+
+    // 同组的第二志愿从未请求，组外课程照常选课
+    assert_eq!(server.enroll_attempts("class-1"), 1);
+    assert_eq!(server.enroll_attempts("class-2"), 0);
+    assert!(server.enroll_attempts("class-3") >= 1);
+    assert!(matches!(
+        report.courses[1].outcome,
+        Some(EnrollOutcome::AlternativeSelected { .. })
+    ));
+    Ok(())
+}
+
+fn refresh_config(param_refreshes: u32) -> EnrollConfig {
+    EnrollConfig {
+        workers: 1,
+        backoff: Backoff {
+            initial: Duration::from_millis(10),
+            max: Duration::from_millis(10),
+            ..Backoff::default()
+        },
+        param_refreshes,
+        ..EnrollConfig::default()
+    }
+}
+
+#[tokio::test]
+async fn engine_refreshes_stale_secret_from_favorites() -> Result<()> {
+    let (server, client) = setup().await?;
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    // 获取收藏后服务器更换了 secretVal
+    let mut fresh = favorites.clone();
+    fresh[0].secret_val = Some("rotated".to_string());
+    server.set_favorites(fresh);
+
+    let engine = EnrollmentEngine::new(session, refresh_config(3));
+    let report = engine.run(&favorites[..1]).await?;
+
+    assert_eq!(server.enroll_attempts(&favorites[0].JXBID), 2);
+    assert_eq!(report.courses[0].outcome, Some(EnrollOutcome::Success));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_stops_on_course_removed_from_favorites() -> Result<()> {
+    let (server, client) = setup().await?;
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+    server.set_favorites(Vec::new());
+
+    let engine = EnrollmentEngine::new(session, refresh_config(3));
+    let mut events = engine.subscribe();
+    let report = engine.run(&favorites[..1]).await?;
+
+    assert_eq!(server.enroll_attempts(&favorites[0].JXBID), 1);
+    assert_eq!(
+        report.courses[0].outcome,
+        Some(EnrollOutcome::RemovedFromFavorites)
+    );
+    let mut finished = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EnrollEvent::CourseFinished { outcome, .. } = event {
+            finished.push(outcome);
+        }
+    }
+    assert_eq!(finished, [EnrollOutcome::RemovedFromFavorites]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn removal_from_favorites_keeps_a_concurrent_success() -> Result<()> {
+    let (server, client) = setup().await?;
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+    server.set_favorites(Vec::new());
+    // 一个线程参数校验失败时另一个线程已经选上
+    server.script_enroll(
+        &favorites[0].JXBID,
+        [MockReply::param_invalid(), MockReply::success()],
+    );
+
+    let config = EnrollConfig {
+        workers: 2,
+        verify: false,
+        ..refresh_config(3)
+    };
+    let report = EnrollmentEngine::new(session, config)
+        .run(&favorites[..1])
+        .await?;
+
+    assert_eq!(server.enroll_attempts(&favorites[0].JXBID), 2);
+    assert_eq!(report.courses[0].outcome, Some(EnrollOutcome::Success));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_gives_up_after_bounded_param_refreshes() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.script_enroll(
+        "class-1",
+        std::iter::repeat_n(MockReply::param_invalid(), 10),
+    );
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+    let course = favorites.iter().find(|c| c.JXBID == "class-1").unwrap();
+
+    let engine = EnrollmentEngine::new(session, refresh_config(2));
+    let report = engine.run(std::slice::from_ref(course)).await?;
+
+    // 刷新两次后第三次仍然失败，不再重试
+    assert_eq!(server.enroll_attempts("class-1"), 3);
+    assert_eq!(report.courses[0].outcome, Some(EnrollOutcome::ParamInvalid));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_confirms_lost_reply_from_selected_list() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.lose_enroll_replies("class-1", 1);
+    // 另一门课已经在选课结果中，但本轮仍在列表里
+    server.set_selected(vec![mock_course("class-2", "大学物理", "李老师")]);
+    server.script_enroll(
+        "class-2",
+        std::iter::repeat_n(MockReply::capacity_full(), 5),
+    );
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let engine = EnrollmentEngine::new(session, refresh_config(3));
+    let mut events = engine.subscribe();
+    let report = engine.run(&favorites).await?;
+
+    // 响应丢失后从选课结果确认，不再重复请求
+    assert_eq!(server.enroll_attempts("class-1"), 1);
+    assert_eq!(server.enroll_attempts("class-2"), 0);
+    assert_eq!(report.courses[0].outcome, Some(EnrollOutcome::Success));
+    assert_eq!(report.courses[0].confirmed, Some(true));
+    assert_eq!(
+        report.courses[1].outcome,
+        Some(EnrollOutcome::AlreadySelected)
+    );
+
+    let mut verified = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        if let EnrollEvent::Verified {
+            class_id,
+            confirmed,
+            ..
+        } = event
+        {
+            verified.push((class_id, confirmed));
+        }
+    }
+    assert_eq!(
+        verified,
+        [("class-1".to_string(), true), ("class-2".to_string(), true)]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_flags_success_missing_from_selected_list() -> Result<()> {
+    let (server, client) = setup().await?;
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+    // 返回成功但没有真正选上
+    server.set_favorites(Vec::new());
+    server.script_enroll("class-1", [MockReply::success()]);
+
+    let engine = EnrollmentEngine::new(session, refresh_config(3));
+    let report = engine.run(&favorites[..1]).await?;
+
+    assert_eq!(report.courses[0].outcome, Some(EnrollOutcome::Success));
+    assert_eq!(report.courses[0].confirmed, Some(false));
+    Ok(())
+}
+
+#[tokio::test]
+async fn engine_retries_lost_reply_without_verification() -> Result<()> {
+    let (server, client) = setup().await?;
+    server.lose_enroll_replies("class-1", 1);
+    let session = MockServer::session_manager_with(client, MockSolver::default());
+    let (_, favorites) = session.get_courses().await?;
+
+    let config = EnrollConfig {
+        verify: false,
+        ..refresh_config(3)
+    };
+    let engine = EnrollmentEngine::new(session, config);
+    let report = engine.run(&favorites[..1]).await?;
+
+    assert_eq!(server.enroll_attempts("class-1"), 2);
+    assert_eq!(
+        report.courses[0].outcome,
+        Some(EnrollOutcome::AlreadySelected)
+    );
+    assert_eq!(report.courses[0].confirmed, None);
+    Ok(())
+}
@@ -0,0 +1,71 @@
+//! Tests for the shared token-bucket rate limiter
+
+use funky_lesson_core::app::login_with_captcha;
+use funky_lesson_core::client::rate_limit::{Endpoint, RateLimit, RateLimiter};
+use funky_lesson_core::client::request::NoWasmClient;
+use funky_lesson_core::error::Result;
+use funky_lesson_core::interface::{HttpClient, RequestApi};
+use funky_lesson_core::testing::{MOCK_PASSWORD, MOCK_USERNAME, MockServer};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+#[tokio::test]
+async fn global_limit_spaces_requests() {
+    let limiter = RateLimiter::new(Some(RateLimit::per_second(50.0)));
+
+    let started = Instant::now();
+    for _ in 0..5 {
+        limiter.acquire(Endpoint::AddCourse).await;
+    }
+    assert!(started.elapsed() >= Duration::from_millis(75));
+
+    let metrics = limiter.metrics();
+    assert_eq!(metrics.total.requests, 5);
+    assert_eq!(metrics.total.throttled, 4);
+    assert!(metrics.total.max_wait <= Duration::from_millis(25));
+    assert_eq!(metrics.endpoints[&Endpoint::AddCourse].requests, 5);
+}
+
+#[tokio::test]
+async fn burst_is_served_immediately() {
+    let limiter = RateLimiter::new(Some(RateLimit::per_second(1.0).with_burst(3)));
+
+    for _ in 0..3 {
+        assert!(limiter.acquire(Endpoint::Select).await.is_zero());
+    }
+    assert_eq!(limiter.metrics().total.throttled, 0);
+}
+
+#[tokio::test]
+async fn endpoint_limit_only_applies_to_its_endpoint() {
+    let limiter =
+        RateLimiter::new(None).with_endpoint(Endpoint::AddCourse, RateLimit::per_second(20.0));
+
+    assert!(limiter.acquire(Endpoint::AddCourse).await.is_zero());
+    assert!(limiter.acquire(Endpoint::Captcha).await.is_zero());
+    assert!(limiter.acquire(Endpoint::Captcha).await.is_zero());
+    assert!(!limiter.acquire(Endpoint::AddCourse).await.is_zero());
+
+    let metrics = limiter.metrics();
+    assert_eq!(metrics.endpoints[&Endpoint::Captcha].throttled, 0);
+    assert_eq!(metrics.endpoints[&Endpoint::AddCourse].throttled, 1);
+}
+
+#[tokio::test]
+async fn client_routes_requests_through_limiter() -> Result<()> {
+    let server = MockServer::start().await?;
+    let limiter = Arc::new(RateLimiter::new(Some(RateLimit::per_second(100.0))));
+    let client = NoWasmClient::with_config(server.server_config())
+        .await?
+        .with_rate_limiter(Arc::clone(&limiter));
+
+    let captcha = client.get_captcha().await?;
+    login_with_captcha(&client, MOCK_USERNAME, MOCK_PASSWORD, "1234", &captcha.uuid).await?;
+
+    let metrics = limiter.metrics();
+    assert_eq!(metrics.total.requests, 3);
+    for endpoint in [Endpoint::Captcha, Endpoint::Index, Endpoint::Login] {
+        assert_eq!(metrics.endpoints[&endpoint].requests, 1);
+    }
+    Ok(())
+}
@@ -0,0 +1,64 @@
+//! Tests for reconciling the favorites with the selected courses
+
+use funky_lesson_core::app::config::resolve_targets;
+use funky_lesson_core::app::reconcile::reconcile;
+use funky_lesson_core::model::structs::{CourseInfo, CourseTarget};
+use funky_lesson_core::testing::mock_course;
+
+fn ids(courses: &[CourseInfo]) -> Vec<&str> {
+    courses.iter().map(|c| c.JXBID.as_str()).collect()
+}
+
+#[test]
+fn favorites_are_split_by_class_and_course() {
+    let favorites = vec![
+        mock_course("class-1", "高等数学", "张老师"),
+        mock_course("class-2", "大学英语", "李老师"),
+        mock_course("class-3", "大学物理", "王老师"),
+        mock_course("class-1", "高等数学", "张老师"),
+        mock_course("class-4", "体育", "赵老师"),
+    ];
+    let selected = vec![
+        mock_course("class-1", "高等数学", "张老师"),
+        mock_course("class-9", "大学英语", "钱老师"),
+    ];
+
+    let reconciliation = reconcile(&favorites, &selected);
+    assert_eq!(ids(&reconciliation.to_enroll), ["class-3", "class-4"]);
+    assert_eq!(ids(&reconciliation.already_selected), ["class-1"]);
+    let [same] = &reconciliation.same_course_selected[..] else {
+        panic!("expected one course with another class selected");
+    };
+    assert_eq!(same.favorite.JXBID, "class-2");
+    assert_eq!(same.selected.JXBID, "class-9");
+    assert!(reconciliation.is_skipped("class-2"));
+    assert!(!reconciliation.is_skipped("class-3"));
+}
+
+#[test]
+fn targets_skip_courses_with_another_class_selected() {
+    let favorites = vec![
+        mock_course("class-1", "体育", "张老师"),
+        mock_course("class-2", "体育", "李老师"),
+        mock_course("class-3", "大学英语", "王老师"),
+        mock_course("class-4", "高等数学", "赵老师"),
+    ];
+    let selected = vec![mock_course("class-8", "大学英语", "钱老师")];
+    let targets = vec![CourseTarget::from("class-3"), CourseTarget::from("class-4")];
+    let groups = vec![vec![
+        CourseTarget::from("class-1"),
+        CourseTarget::from("class-2"),
+    ]];
+
+    let open = resolve_targets(&targets, &groups, &favorites, &selected);
+    assert_eq!(ids(&open.courses), ["class-1", "class-2", "class-4"]);
+    assert_eq!(open.same_course_selected.len(), 1);
+    assert_eq!(open.groups.len(), 1);
+
+    // 同组课程的其他教学班已选上，整组不再尝试
+    let selected = vec![mock_course("class-7", "体育", "孙老师")];
+    let satisfied = resolve_targets(&targets, &groups, &favorites, &selected);
+    assert_eq!(ids(&satisfied.courses), ["class-3", "class-4"]);
+    assert!(satisfied.groups.is_empty());
+    assert_eq!(satisfied.same_course_selected.len(), 2);
+}
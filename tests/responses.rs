@@ -0,0 +1,113 @@
+//! Deserialization tests for the typed response models
+
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::model::responses::{ApiResponse, CourseListResponse, LoginResponse};
+use funky_lesson_core::model::structs::EnrollOutcome;
+
+#[test]
+fn login_response_deserializes_student_and_batches() {
+    let resp: LoginResponse = serde_json::from_str(
+        r#"{
+            "code": 200,
+            "msg": "登录成功",
+            "data": {
+                "token": "abc",
+                "student": {
+                    "XH": "20240001",
+                    "XM": "测试学生",
+                    "ZYMC": "软件工程",
+                    "electiveBatchList": [
+                        {"code": "b1", "name": "第一轮", "beginTime": "2025-08-20 13:00:00", "endTime": "2025-08-22 17:00:00"}
+                    ]
+                }
+            }
+        }"#,
+    )
+    .unwrap();
+
+    let data = resp.into_data().unwrap();
+    assert_eq!(data.token, "abc");
+    assert_eq!(data.student.ZYMC, "软件工程");
+    assert_eq!(data.student.elective_batch_list[0].code, "b1");
+}
+
+#[test]
+fn schema_drift_is_a_deserialization_error() {
+    let err = serde_json::from_str::<LoginResponse>(
+        r#"{"code": 200, "msg": "登录成功", "data": {"token": "abc"}}"#,
+    )
+    .unwrap_err();
+
+    assert!(err.to_string().contains("missing field `student`"));
+}
+
+#[test]
+fn failed_response_surfaces_server_message() {
+    let resp: CourseListResponse =
+        serde_json::from_str(r#"{"code": 500, "msg": "系统繁忙", "data": null}"#).unwrap();
+
+    assert!(!resp.is_success());
+    let err = resp.into_data().unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::CourseError(msg) if msg == "系统繁忙"));
+}
+
+#[test]
+fn unauthorized_response_is_token_expired() {
+    let resp: CourseListResponse =
+        serde_json::from_str(r#"{"code": 401, "msg": "请先登录", "data": null}"#).unwrap();
+
+    assert!(resp.into_data().unwrap_err().is_token_expired());
+}
+
+#[test]
+fn envelope_without_data_defaults_to_none() {
+    let resp: ApiResponse = serde_json::from_str(r#"{"code": 200, "msg": "操作成功"}"#).unwrap();
+
+    assert!(resp.is_success());
+    assert!(resp.data.is_none());
+}
+
+#[test]
+fn enroll_outcome_classifies_server_replies() {
+    let cases = [
+        (200, "选课成功", EnrollOutcome::Success),
+        (500, "该课程已在选课结果中", EnrollOutcome::AlreadySelected),
+        (500, "本轮次选课暂未开始", EnrollOutcome::NotStarted),
+        (500, "课容量已满", EnrollOutcome::CapacityFull),
+        (500, "参数校验不通过", EnrollOutcome::ParamInvalid),
+        (401, "请先登录", EnrollOutcome::Unauthorized),
+    ];
+    for (code, msg, expected) in cases {
+        assert_eq!(EnrollOutcome::from_response(code, msg), expected);
+    }
+
+    assert_eq!(
+        EnrollOutcome::from_response(500, "时间冲突"),
+        EnrollOutcome::Unknown {
+            code: 500,
+            msg: "时间冲突".to_string()
+        }
+    );
+}
+
+#[test]
+fn enroll_outcome_classifies_transport_errors() {
+    let result: Result<ApiResponse> = Err(ErrorKind::ParseError("timeout".to_string()).into());
+
+    let outcome = EnrollOutcome::classify(&result);
+    assert!(matches!(outcome, EnrollOutcome::Transport { .. }));
+    assert!(!outcome.is_selected());
+    assert!(EnrollOutcome::AlreadySelected.is_selected());
+}
+
+#[test]
+fn error_chains_underlying_source() {
+    use std::error::Error as _;
+
+    let json_err = serde_json::from_str::<ApiResponse>("not json").unwrap_err();
+    let err = funky_lesson_core::error::Error::from(json_err);
+    assert!(err.source().is_some());
+
+    let err = funky_lesson_core::error::Error::from(ErrorKind::TokenExpired);
+    assert!(err.source().is_none());
+}
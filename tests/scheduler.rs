@@ -0,0 +1,115 @@
+//! Tests for the batch-window scheduler
+
+use chrono::{TimeDelta, Timelike, Utc};
+use funky_lesson_core::app::engine::{EnrollConfig, EnrollEvent, EnrollmentEngine};
+use funky_lesson_core::app::scheduler::{ScheduleConfig, Scheduler, parse_batch_time, shanghai};
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::testing::{MockReply, MockServer, MockSolver, mock_batch, mock_course};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[test]
+fn batch_time_is_shanghai_local_time() {
+    let time = parse_batch_time("2025-08-20 13:00:00").unwrap();
+
+    assert_eq!(time.offset(), &shanghai());
+    assert_eq!(time.with_timezone(&Utc).hour(), 5);
+}
+
+#[test]
+fn invalid_batch_time_is_parse_error() {
+    let err = parse_batch_time("2025/08/20").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::ParseError(_)));
+}
+
+#[tokio::test]
+async fn scheduler_waits_for_begin_and_stops_after_end() -> Result<()> {
+    let server = MockServer::start().await?;
+    let now = Utc::now()
+        .with_timezone(&shanghai())
+        .with_nanosecond(0)
+        .unwrap();
+    let begin = now + TimeDelta::seconds(2);
+    let end = begin + TimeDelta::seconds(1);
+    server.set_batches(vec![mock_batch(
+        "batch-1",
+        "第一轮选课",
+        &begin.format(FORMAT).to_string(),
+        &end.format(FORMAT).to_string(),
+    )]);
+    server.set_favorites(vec![mock_course("class-1", "高等数学", "张老师")]);
+    server.script_enroll(
+        "class-1",
+        std::iter::repeat_n(MockReply::not_started(), 1000),
+    );
+
+    let session = server.session_manager(MockSolver::default()).await?;
+    let config = ScheduleConfig {
+        lead_time: Duration::from_millis(300),
+        warm_up: Duration::from_millis(600),
+        stop_at_end: true,
+    };
+    let scheduler = Scheduler::new(Arc::clone(&session), config);
+    let engine = EnrollmentEngine::new(
+        session,
+        EnrollConfig {
+            workers: 1,
+            interval: Duration::from_millis(50),
+            ..EnrollConfig::default()
+        },
+    );
+    let mut events = engine.subscribe();
+    let courses = vec![mock_course("class-1", "高等数学", "张老师")];
+
+    let until_begin = (begin - Utc::now().with_timezone(&shanghai()))
+        .to_std()
+        .unwrap();
+    let started = Instant::now();
+    let report = scheduler.run(&engine, &courses).await?;
+    let elapsed = started.elapsed();
+
+    let mut kinds = Vec::new();
+    while let Ok(event) = events.try_recv() {
+        let kind = match event {
+            EnrollEvent::WarmingUp => "warm_up",
+            EnrollEvent::Starting { .. } => "start",
+            EnrollEvent::Attempt { .. } => "attempt",
+            EnrollEvent::BatchEnded => "end",
+            EnrollEvent::Finished(_) => "finished",
+            _ => continue,
+        };
+        if kinds.last() != Some(&kind) {
+            kinds.push(kind);
+        }
+    }
+    // 调度进度和选课结果在同一个事件流中
+    assert_eq!(kinds, ["warm_up", "start", "attempt", "end", "finished"]);
+    assert!(report.total_requests > 0);
+    // No request before `begin - lead_time`, none long after `end`
+    assert!(elapsed >= until_begin.saturating_sub(Duration::from_millis(350)));
+    assert!(elapsed < until_begin + Duration::from_millis(1600));
+    assert!(server.selected().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduler_rejects_ended_batch() -> Result<()> {
+    let server = MockServer::start().await?;
+    server.set_batches(vec![mock_batch(
+        "batch-1",
+        "第一轮选课",
+        "2020-01-01 08:00:00",
+        "2020-01-02 08:00:00",
+    )]);
+
+    let session = server.session_manager(MockSolver::default()).await?;
+    let scheduler = Scheduler::new(Arc::clone(&session), ScheduleConfig::default());
+    let engine = EnrollmentEngine::new(session, EnrollConfig::default());
+
+    let err = scheduler.run(&engine, &[]).await.unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::BatchEnded { .. }));
+    assert_eq!(server.enroll_attempts("class-1"), 0);
+    Ok(())
+}
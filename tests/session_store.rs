@@ -0,0 +1,90 @@
+//! Tests for saving and resuming sessions across runs
+
+use funky_lesson_core::app::store::SessionStore;
+use funky_lesson_core::error::Result;
+use funky_lesson_core::testing::{MOCK_USERNAME, MockServer, MockSolver, scratch_dir};
+
+#[tokio::test]
+async fn saved_session_resumes_without_captcha() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("session-resume")?;
+    let store = SessionStore::new(dir.join("session.json"));
+    let solver = MockSolver::default();
+
+    let first = server.session_manager(solver.clone()).await?;
+    first.current().await?;
+    store.save(&first.snapshot().await.unwrap())?;
+
+    let saved = store.load()?.unwrap();
+    assert_eq!(saved.username.as_deref(), Some(MOCK_USERNAME));
+    assert_eq!(saved.session.batch_id, "batch-1");
+    assert!(!saved.cookies.is_empty());
+
+    let second = server.session_manager(solver.clone()).await?;
+    assert!(second.resume(saved).await?);
+    second.get_courses().await?;
+
+    assert_eq!(solver.calls(), 1);
+    assert_eq!(server.login_count(), 1);
+    // 恢复的 cookie 会随请求发送
+    assert!(
+        server
+            .last_cookie()
+            .is_some_and(|cookie| cookie.contains("JSESSIONID=mock-session-1"))
+    );
+
+    store.clear()?;
+    assert!(store.load()?.is_none());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_session_falls_back_to_login() -> Result<()> {
+    let server = MockServer::start().await?;
+    let dir = scratch_dir("session-expired")?;
+    let store = SessionStore::new(dir.join("session.json"));
+    let solver = MockSolver::default();
+
+    let first = server.session_manager(solver.clone()).await?;
+    first.current().await?;
+    store.save(&first.snapshot().await.unwrap())?;
+    server.expire_tokens();
+
+    let second = server.session_manager(solver.clone()).await?;
+    assert!(!second.resume(store.load()?.unwrap()).await?);
+    assert!(!second.validate().await?);
+
+    let session = second.current().await?;
+    assert_eq!(session.token, "mock-token-2");
+    assert_eq!(solver.calls(), 2);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn session_file_is_private() -> Result<()> {
+    use funky_lesson_core::app::session::Session;
+    use funky_lesson_core::app::store::SavedSession;
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = scratch_dir("session-private")?;
+    let path = dir.join("session.json");
+    std::fs::write(&path, "{}")?;
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+
+    let session: Session = serde_json::from_str(r#"{"token": "t", "batch_id": "b"}"#).unwrap();
+    let store = SessionStore::new(&path);
+    store.save(&SavedSession {
+        username: None,
+        session,
+        cookies: Vec::new(),
+    })?;
+
+    let mode = std::fs::metadata(&path)?.permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
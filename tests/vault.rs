@@ -0,0 +1,103 @@
+//! Tests for the encrypted credential vault
+
+use funky_lesson_core::app::vault::{CredentialVault, KdfParams};
+use funky_lesson_core::error::{ErrorKind, Result};
+use funky_lesson_core::testing::scratch_dir;
+
+/// Cheap parameters so the tests stay fast in debug builds
+fn fast_kdf() -> KdfParams {
+    KdfParams {
+        m_cost: 64,
+        t_cost: 1,
+        p_cost: 1,
+    }
+}
+
+#[test]
+fn passwords_round_trip_and_stay_encrypted() -> Result<()> {
+    let dir = scratch_dir("vault-round-trip")?;
+    let path = dir.join("vault.json");
+    let mut vault = CredentialVault::create(&path, "passphrase", fast_kdf())?;
+    assert!(!vault.insert("2023000001", "hunter2")?);
+    assert!(vault.insert("2023000001", "hunter3")?);
+    vault.insert("2023000002", "correct horse")?;
+    vault.save()?;
+
+    let content = std::fs::read_to_string(&path)?;
+    assert!(!content.contains("hunter3"));
+    assert_eq!(
+        CredentialVault::list(&path)?,
+        vec!["2023000001".to_string(), "2023000002".to_string()]
+    );
+
+    let vault = CredentialVault::open(&path, "passphrase")?;
+    assert_eq!(vault.get("2023000001")?.as_deref(), Some("hunter3"));
+    assert_eq!(vault.get("2023000002")?.as_deref(), Some("correct horse"));
+    assert_eq!(vault.get("2023000003")?, None);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path)?.permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn wrong_passphrase_is_rejected() -> Result<()> {
+    let dir = scratch_dir("vault-wrong")?;
+    let path = dir.join("vault.json");
+    let mut vault = CredentialVault::create(&path, "passphrase", fast_kdf())?;
+    vault.insert("2023000001", "hunter2")?;
+    vault.save()?;
+
+    let err = CredentialVault::open(&path, "not the passphrase").unwrap_err();
+    assert!(matches!(err.kind(), ErrorKind::VaultLocked));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn entries_cannot_be_swapped_between_accounts() -> Result<()> {
+    let dir = scratch_dir("vault-swap")?;
+    let path = dir.join("vault.json");
+    let mut vault = CredentialVault::create(&path, "passphrase", fast_kdf())?;
+    vault.insert("alice", "alice-password")?;
+    vault.insert("bob", "bob-password")?;
+    vault.save()?;
+
+    // 把 alice 的密文挪到 bob 名下，认证标签应当校验失败
+    let mut file: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+    let alice = file["entries"]["alice"].clone();
+    file["entries"]["bob"] = alice;
+    std::fs::write(&path, serde_json::to_vec(&file)?)?;
+
+    let vault = CredentialVault::open(&path, "passphrase")?;
+    assert_eq!(vault.get("alice")?.as_deref(), Some("alice-password"));
+    assert!(matches!(
+        vault.get("bob").unwrap_err().kind(),
+        ErrorKind::VaultLocked
+    ));
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[test]
+fn removed_accounts_are_gone_after_save() -> Result<()> {
+    let dir = scratch_dir("vault-remove")?;
+    let path = dir.join("vault.json");
+    let mut vault = CredentialVault::create(&path, "passphrase", fast_kdf())?;
+    vault.insert("2023000001", "hunter2")?;
+    vault.save()?;
+
+    let mut vault = CredentialVault::open(&path, "passphrase")?;
+    assert!(vault.remove("2023000001"));
+    assert!(!vault.remove("2023000001"));
+    vault.save()?;
+    assert!(CredentialVault::list(&path)?.is_empty());
+    assert!(CredentialVault::list(dir.join("missing.json"))?.is_empty());
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}